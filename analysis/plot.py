@@ -3,7 +3,7 @@ import numpy as np
 import matplotlib.pyplot as plt
 
 
-def parse(kernel, calc_cnt=1):
+def parse(kernel, calc_cnt=1, start_idx=18, end_idx=None):
     with open(f"{kernel}.json", "r") as file:
         data = json.load(file)
 
@@ -19,12 +19,10 @@ def parse(kernel, calc_cnt=1):
     p_diff_max = np.array(p_diff_max)
     v_diff_max = np.array(v_diff_max)
 
-    start_idx = 18
-
     # Fit a line to the log-log data
-    log_t = np.log(t[start_idx:])
-    log_p_diff_max = np.log(p_diff_max[start_idx:])
-    log_v_diff_max = np.log(v_diff_max[start_idx:])
+    log_t = np.log(t[start_idx:end_idx])
+    log_p_diff_max = np.log(p_diff_max[start_idx:end_idx])
+    log_v_diff_max = np.log(v_diff_max[start_idx:end_idx])
 
     # Fit the data using a linear regression (polyfit with degree 1)
     p_fit = np.polyfit(log_t, log_p_diff_max, 1)
@@ -59,16 +57,69 @@ def parse(kernel, calc_cnt=1):
     plt.yscale("log")
 
 
+def parse_energy(kernel, calc_cnt=1):
+    with open(f"{kernel}.json", "r") as file:
+        data = json.load(file)
+
+    t = np.array([i["dt"] * calc_cnt for i in data])
+    energy_error = np.array([i["energy_error"] for i in data])
+
+    color = plt.plot(t, energy_error, label=f"{kernel} energy")[0].get_color()
+    plt.scatter(t, energy_error, color=color)
+
+    plt.xlabel("dt")
+
+    plt.xscale("log")
+    plt.yscale("log")
+
+
 if __name__ == "__main__":
+    parse("yoshida4_compensated", 3)
     parse("yoshida4_relative", 3)
     parse("yoshida4", 3)
+    parse("yoshida6_relative", 7, 21)
+    parse("yoshida6", 7, 21)
+    # Solution A leaves the asymptotic regime early due to its large weights.
+    parse("yoshida8_relative", 15, 19, 23)
+    parse("yoshida8", 15, 19, 23)
+    parse("kahan_li6_relative", 9, 21)
+    parse("kahan_li6", 9, 21)
+    parse("kahan_li8_relative", 17, 23)
+    parse("kahan_li8", 17, 23)
+    parse("suzuki6_relative", 25, 21)
+    parse("suzuki6", 25, 21)
+    parse("suzuki8_relative", 125, 23)
+    parse("suzuki8", 125, 23)
+    parse("pefrl_relative", 4)
+    parse("pefrl", 4)
+    parse("mclachlan_relative", 2)
+    parse("mclachlan", 2)
+    parse("blanes_moan4_relative", 6, 20)
+    parse("blanes_moan4", 6, 20)
+    parse("blanes_moan6_relative", 11, 21)
+    parse("blanes_moan6", 11, 21)
+    parse("vel_verlet_compensated")
     parse("vel_verlet_relative")
     parse("vel_verlet")
+    parse("symplectic_euler_compensated")
     parse("symplectic_euler_relative")
     parse("symplectic_euler")
     parse("rk4", 4)
+    parse("bulirsch_stoer", 17)
     plt.ylim(bottom=1e-10)
     box = plt.gca().get_position()
     plt.gca().set_position([box.x0, box.y0, box.width * 0.8, box.height])
     plt.legend(loc="center left", bbox_to_anchor=(1, 0.5))
     plt.show()
+
+    # Round-off: the compensated kernels against the relative ones, whose
+    # energy drifts at small dt as the rounding of the increments adds up.
+    plt.figure()
+    parse_energy("yoshida4_compensated", 3)
+    parse_energy("yoshida4_relative", 3)
+    parse_energy("vel_verlet_compensated")
+    parse_energy("vel_verlet_relative")
+    parse_energy("symplectic_euler_compensated")
+    parse_energy("symplectic_euler_relative")
+    plt.legend()
+    plt.show()
@@ -0,0 +1,382 @@
+use core::fmt::Debug;
+use core::fmt::Display;
+use core::ops::*;
+
+use crate::Vec3;
+
+/// Unevaluated sum `hi + lo` of two doubles with `|lo| <= ulp(hi) / 2`,
+/// about 106 bits of precision (Dekker 1971, Hida, Li & Bailey 2001), for
+/// reference solutions beyond the round-off of `f64`.
+///
+/// The operations use the error-free transformations of the sum and of the
+/// product, the latter through the fused multiply-add.
+#[derive(Default, Clone, Copy, PartialEq, PartialOrd)]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+/// `a + b = s + e` exactly.
+#[inline]
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let bb = s - a;
+    return (s, (a - (s - bb)) + (b - bb));
+}
+
+/// `a + b = s + e` exactly, for `|a| >= |b|`.
+#[inline]
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    return (s, b - (s - a));
+}
+
+/// `a * b = p + e` exactly.
+#[inline]
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+    let p = a * b;
+    return (p, a.mul_add(b, -p));
+}
+
+impl DoubleDouble {
+    pub const ZERO: DoubleDouble = DoubleDouble { hi: 0.0, lo: 0.0 };
+    pub const ONE: DoubleDouble = DoubleDouble { hi: 1.0, lo: 0.0 };
+
+    #[inline]
+    fn renormalize((hi, lo): (f64, f64)) -> Self {
+        let (hi, lo) = quick_two_sum(hi, lo);
+        DoubleDouble { hi: hi, lo: lo }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn abs(self) -> Self {
+        if self.hi < 0.0 {
+            -self
+        } else {
+            self
+        }
+    }
+
+    /// Square root by one Newton step from the `f64` square root.
+    #[inline]
+    #[must_use]
+    pub fn sqrt(self) -> Self {
+        if self.hi <= 0.0 {
+            return DoubleDouble::from(self.hi.sqrt());
+        }
+        let s = self.hi.sqrt();
+        let (p, e) = two_prod(s, s);
+        let r = (self - DoubleDouble { hi: p, lo: e }).hi;
+        return DoubleDouble::renormalize((s, r / (2.0 * s)));
+    }
+}
+
+impl From<f64> for DoubleDouble {
+    #[inline]
+    fn from(value: f64) -> Self {
+        DoubleDouble { hi: value, lo: 0.0 }
+    }
+}
+
+impl Debug for DoubleDouble {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "DoubleDouble({:.16e}, {:.16e})", self.hi, self.lo)
+    }
+}
+
+impl Display for DoubleDouble {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:.16e}", self.hi)
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        let (s1, s2) = two_sum(self.hi, rhs.hi);
+        let (t1, t2) = two_sum(self.lo, rhs.lo);
+        let (s1, s2) = quick_two_sum(s1, s2 + t1);
+        return DoubleDouble::renormalize((s1, s2 + t2));
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        self + -rhs
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        let (p, e) = two_prod(self.hi, rhs.hi);
+        let e = self.hi.mul_add(rhs.lo, self.lo.mul_add(rhs.hi, e));
+        return DoubleDouble::renormalize((p, e));
+    }
+}
+
+impl Mul<f64> for DoubleDouble {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: f64) -> Self {
+        let (p, e) = two_prod(self.hi, rhs);
+        return DoubleDouble::renormalize((p, self.lo.mul_add(rhs, e)));
+    }
+}
+
+impl Div for DoubleDouble {
+    type Output = Self;
+
+    /// Long division, with a third quotient digit for full accuracy.
+    #[inline]
+    fn div(self, rhs: Self) -> Self {
+        let q1 = self.hi / rhs.hi;
+        let r = self - rhs * q1;
+        let q2 = r.hi / rhs.hi;
+        let r = r - rhs * q2;
+        let q3 = r.hi / rhs.hi;
+        return DoubleDouble::renormalize((q1, q2)) + DoubleDouble::from(q3);
+    }
+}
+
+impl Div<f64> for DoubleDouble {
+    type Output = Self;
+
+    #[inline]
+    fn div(self, rhs: f64) -> Self {
+        self / DoubleDouble::from(rhs)
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        DoubleDouble {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+}
+
+/// `Vec3` in double-double precision, with the same API, for the kernels
+/// generic over precision. It is plain scalar code and many times slower
+/// than `Vec3`.
+#[derive(Default, Clone, Copy, PartialEq)]
+pub struct Vec3DD {
+    pub x: DoubleDouble,
+    pub y: DoubleDouble,
+    pub z: DoubleDouble,
+}
+
+impl Vec3DD {
+    pub const ZERO: Vec3DD = Vec3DD {
+        x: DoubleDouble::ZERO,
+        y: DoubleDouble::ZERO,
+        z: DoubleDouble::ZERO,
+    };
+    pub const ONE: Vec3DD = Vec3DD {
+        x: DoubleDouble::ONE,
+        y: DoubleDouble::ONE,
+        z: DoubleDouble::ONE,
+    };
+
+    #[must_use]
+    #[inline]
+    pub fn new(x: DoubleDouble, y: DoubleDouble, z: DoubleDouble) -> Self {
+        Vec3DD { x, y, z }
+    }
+
+    pub fn splat(value: DoubleDouble) -> Self {
+        Self::new(value, value, value)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn reduce_add(&self) -> DoubleDouble {
+        self.x + self.y + self.z
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn norm_squared(&self) -> DoubleDouble {
+        let squared = self * self;
+        squared.reduce_add()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn norm(&self) -> DoubleDouble {
+        self.norm_squared().sqrt()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn mul_add(a: Vec3DD, b: Vec3DD, c: Vec3DD) -> Vec3DD {
+        a * b + c
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn mul_sub(a: Vec3DD, b: Vec3DD, c: Vec3DD) -> Vec3DD {
+        a * b - c
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn mul_neg_add(a: Vec3DD, b: Vec3DD, c: Vec3DD) -> Vec3DD {
+        c - a * b
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn mul_neg_sub(a: Vec3DD, b: Vec3DD, c: Vec3DD) -> Vec3DD {
+        -(a * b) - c
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn calc_r(p1: &Vec3DD, p2: &Vec3DD) -> Vec3DD {
+        let r = p2 - p1;
+        let r2 = r.norm_squared();
+        let mag = r2 * r2.sqrt();
+        return r / mag;
+    }
+}
+
+impl From<Vec3> for Vec3DD {
+    #[inline]
+    fn from(value: Vec3) -> Self {
+        let [x, y, z] = <[f64; 3]>::from(value);
+        Vec3DD::new(x.into(), y.into(), z.into())
+    }
+}
+
+impl From<Vec3DD> for Vec3 {
+    #[inline]
+    fn from(value: Vec3DD) -> Self {
+        Vec3::new(value.x.to_f64(), value.y.to_f64(), value.z.to_f64())
+    }
+}
+
+impl Debug for Vec3DD {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Vec3DD({:?}, {:?}, {:?})", self.x, self.y, self.z)
+    }
+}
+
+impl Display for Vec3DD {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "({}, {}, {})", self.x, self.y, self.z)
+    }
+}
+
+impl Add for Vec3DD {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        Vec3DD {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl Sub for Vec3DD {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        Vec3DD {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
+
+impl Mul<DoubleDouble> for Vec3DD {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: DoubleDouble) -> Self {
+        Vec3DD {
+            x: self.x * rhs,
+            y: self.y * rhs,
+            z: self.z * rhs,
+        }
+    }
+}
+
+impl Mul<Vec3DD> for DoubleDouble {
+    type Output = Vec3DD;
+    fn mul(self, rhs: Vec3DD) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl Mul for Vec3DD {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        Vec3DD {
+            x: self.x * rhs.x,
+            y: self.y * rhs.y,
+            z: self.z * rhs.z,
+        }
+    }
+}
+
+impl Div<DoubleDouble> for Vec3DD {
+    type Output = Self;
+
+    #[inline]
+    fn div(self, rhs: DoubleDouble) -> Self {
+        Vec3DD {
+            x: self.x / rhs,
+            y: self.y / rhs,
+            z: self.z / rhs,
+        }
+    }
+}
+
+impl Div for Vec3DD {
+    type Output = Self;
+
+    #[inline]
+    fn div(self, rhs: Self) -> Self {
+        Vec3DD {
+            x: self.x / rhs.x,
+            y: self.y / rhs.y,
+            z: self.z / rhs.z,
+        }
+    }
+}
+
+impl Neg for Vec3DD {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        Self::ZERO - self
+    }
+}
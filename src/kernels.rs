@@ -1,16 +1,124 @@
+use std::sync::Arc;
+
+use crate::util;
 use crate::Vec3;
 
+mod events;
+mod external;
+mod force_law;
+pub mod n_body;
+mod post_newtonian;
+mod softening;
 pub mod three_body;
 
+pub use events::*;
+pub use external::*;
+pub use force_law::*;
+pub use post_newtonian::*;
+pub use softening::*;
+
 #[derive(Clone, Debug)]
 pub struct PhysicsState {
     pub p: Vec<Vec3>,
     pub v: Vec<Vec3>,
     pub m: Vec<f64>,
+    /// Radius of each body in m, zero for point masses. Only used for
+    /// collisions.
+    pub r: Vec<f64>,
+    /// Number of test particles, the last bodies, which have no mass: they
+    /// feel the massive bodies but act on nothing, so the N-body kernels only
+    /// sum the `N_massive * N_test` interactions they take part in. The
+    /// three-body kernels treat them as ordinary massless bodies.
+    pub n_test: usize,
     pub t: u64,
+    /// Time past `t` in seconds, below one tick, for kernels whose steps are
+    /// not a whole number of ticks.
+    pub t_frac: f64,
+    pub softening: Softening,
+    pub post_newtonian: PostNewtonian,
+    pub external: Vec<Arc<dyn ExternalPotential>>,
+    /// Pairwise interaction in place of Newtonian gravity, if any.
+    pub force_law: Option<Arc<dyn ForceLaw>>,
+    /// Event functions located by `kernel_events`, reported by their index.
+    pub events: Vec<Arc<dyn EventFunction>>,
+}
+
+/// Advances the time `t` ticks plus `t_frac` seconds by `dt` seconds, keeping
+/// the fraction below one tick.
+pub fn advance_time(t: u64, t_frac: f64, dt: f64) -> (u64, f64) {
+    let t_frac = t_frac + dt;
+    let ticks = (t_frac / util::UNIT_TIME).floor();
+    return (t + ticks as u64, t_frac - ticks * util::UNIT_TIME);
+}
+
+/// What a kernel takes into account beyond Newtonian gravity between point
+/// masses, for `PhysicsState::assert_newtonian_only`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Extension {
+    External,
+    ForceLaw,
+}
+
+/// The check behind `PhysicsState::assert_newtonian_only` and
+/// `ThreeBodyState::assert_newtonian_only`.
+fn assert_newtonian_only(
+    kernel: &str,
+    handled: &[Extension],
+    post_newtonian: &PostNewtonian,
+    external: &[Arc<dyn ExternalPotential>],
+    force_law: &Option<Arc<dyn ForceLaw>>,
+) {
+    assert!(
+        !post_newtonian.is_enabled(),
+        "{} doesn't support post-Newtonian corrections",
+        kernel
+    );
+    assert!(
+        external.is_empty() || handled.contains(&Extension::External),
+        "{} doesn't support external potentials",
+        kernel
+    );
+    assert!(
+        force_law.is_none() || handled.contains(&Extension::ForceLaw),
+        "{} doesn't support force laws other than Newtonian gravity",
+        kernel
+    );
 }
 
 impl PhysicsState {
+    /// Time in seconds.
+    pub fn time(&self) -> f64 {
+        return self.t as f64 * util::UNIT_TIME + self.t_frac;
+    }
+
+    /// Panics naming `kernel` if the state needs more than Newtonian gravity
+    /// between point masses and the extensions in `handled`. Post-Newtonian
+    /// corrections are never handled, since the kernels calling this assume
+    /// accelerations depending on the positions only. They call it so that
+    /// they refuse a state instead of quietly integrating different dynamics.
+    pub fn assert_newtonian_only(&self, kernel: &str, handled: &[Extension]) {
+        assert_newtonian_only(
+            kernel,
+            handled,
+            &self.post_newtonian,
+            &self.external,
+            &self.force_law,
+        );
+    }
+
+    pub fn n_massive(&self) -> usize {
+        return self.p.len() - self.n_test;
+    }
+
+    /// Adds a test particle after the bodies already there.
+    pub fn push_test_particle(&mut self, p: Vec3, v: Vec3) {
+        self.p.push(p);
+        self.v.push(v);
+        self.m.push(0.0);
+        self.r.push(0.0);
+        self.n_test += 1;
+    }
+
     pub fn calc_kinetic_energy(&self) -> f64 {
         let mut e_k = 0.0;
         for i in 0..self.p.len() {
@@ -21,10 +129,24 @@ impl PhysicsState {
 
     pub fn calc_potential_energy(&self) -> f64 {
         let mut e_p = 0.0;
-        for i in 0..self.p.len() {
-            for j in (i + 1)..self.p.len() {
-                let r = self.p[i] - self.p[j];
-                e_p -= self.m[i] * self.m[j] / r.norm();
+        let mu: Vec<f64> = self.m.iter().map(|m| m * util::GRAVITY_CONSTANT).collect();
+        // Test particles have no mass, so they add nothing.
+        let n_massive = self.n_massive();
+        for i in 0..n_massive {
+            for j in (i + 1)..n_massive {
+                let r2 = (self.p[i] - self.p[j]).norm_squared();
+                match &self.force_law {
+                    None => {
+                        e_p -= util::GRAVITY_CONSTANT
+                            * self.m[i]
+                            * self.m[j]
+                            * self.softening.potential(r2);
+                    }
+                    Some(force_law) => e_p += force_law.potential(&mu, i, j, r2),
+                }
+            }
+            for potential in &self.external {
+                e_p += self.m[i] * potential.potential(&self.p[i]);
             }
         }
         return e_p;
@@ -0,0 +1,204 @@
+use std::fmt::Debug;
+
+use crate::Vec3;
+
+const MAX_ITERATIONS: usize = 100;
+
+/// A scalar function of the positions and velocities whose zeros are events,
+/// such as a close approach or a body crossing a plane.
+///
+/// The event functions of a state are checked by the three-body kernels'
+/// `kernel_events`, which evaluates them at the ends of every step of the
+/// dense output and locates each sign change on it with `find_root`. A
+/// function changing sign twice within a step, or only touching zero, is
+/// missed, so the steps should be short against the time between events.
+pub trait EventFunction: Debug + Send + Sync {
+    /// Value at the positions `p` and velocities `v` of all bodies.
+    fn value(&self, p: &[Vec3], v: &[Vec3]) -> f64;
+
+    /// Which sign changes are events.
+    fn direction(&self) -> Direction {
+        return Direction::Both;
+    }
+
+    /// Whether the integration stops at the event.
+    fn terminal(&self) -> bool {
+        return false;
+    }
+}
+
+/// Direction in which an event function crosses zero.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Increasing,
+    Decreasing,
+    Both,
+}
+
+impl Direction {
+    /// Whether the change from `g0` to `g1` crosses zero in this direction. A
+    /// zero at the start does not count, so an integration stopped at an
+    /// event does not find it again.
+    pub fn crosses(&self, g0: f64, g1: f64) -> bool {
+        let increasing = g0 < 0.0 && g1 >= 0.0;
+        let decreasing = g0 > 0.0 && g1 <= 0.0;
+        return match self {
+            Direction::Increasing => increasing,
+            Direction::Decreasing => decreasing,
+            Direction::Both => increasing || decreasing,
+        };
+    }
+}
+
+/// An event found by `kernel_events`.
+#[derive(Clone, Debug)]
+pub struct Event {
+    /// Index of the event function in the state.
+    pub event: usize,
+    /// Time in seconds.
+    pub t: f64,
+    pub p: Vec<Vec3>,
+    pub v: Vec<Vec3>,
+}
+
+/// Closest approach of bodies `i` and `j`, where the separation stops
+/// shrinking, `(p_j - p_i) . (v_j - v_i) = 0` going up.
+#[derive(Clone, Copy, Debug)]
+pub struct CloseApproach {
+    pub i: usize,
+    pub j: usize,
+    pub terminal: bool,
+}
+
+impl EventFunction for CloseApproach {
+    fn value(&self, p: &[Vec3], v: &[Vec3]) -> f64 {
+        return ((p[self.j] - p[self.i]) * (v[self.j] - v[self.i])).reduce_add();
+    }
+
+    fn direction(&self) -> Direction {
+        return Direction::Increasing;
+    }
+
+    fn terminal(&self) -> bool {
+        return self.terminal;
+    }
+}
+
+/// Body `i` moving out past `radius` from the origin. Whether it is unbound
+/// is left to the caller, from the velocity in the event.
+#[derive(Clone, Copy, Debug)]
+pub struct Escape {
+    pub i: usize,
+    pub radius: f64,
+    pub terminal: bool,
+}
+
+impl EventFunction for Escape {
+    fn value(&self, p: &[Vec3], _v: &[Vec3]) -> f64 {
+        return p[self.i].norm() - self.radius;
+    }
+
+    fn direction(&self) -> Direction {
+        return Direction::Increasing;
+    }
+
+    fn terminal(&self) -> bool {
+        return self.terminal;
+    }
+}
+
+/// Body `i` crossing the plane `normal . p = offset`, increasing being along
+/// `normal`. The plane `z = 0` crossed upwards gives the ascending nodes.
+#[derive(Clone, Copy, Debug)]
+pub struct PlaneCrossing {
+    pub i: usize,
+    pub normal: Vec3,
+    pub offset: f64,
+    pub direction: Direction,
+    pub terminal: bool,
+}
+
+impl EventFunction for PlaneCrossing {
+    fn value(&self, p: &[Vec3], _v: &[Vec3]) -> f64 {
+        return (self.normal * p[self.i]).reduce_add() - self.offset;
+    }
+
+    fn direction(&self) -> Direction {
+        return self.direction;
+    }
+
+    fn terminal(&self) -> bool {
+        return self.terminal;
+    }
+}
+
+/// Zero of `f` between `a` and `b`, given `fa = f(a)` and `fb = f(b)` of
+/// opposite signs, by Brent's method (Brent 1973) to roundoff. Returns the
+/// end of the final bracket on the side of `b`, where `f` has already changed
+/// sign.
+pub fn find_root<F: Fn(f64) -> f64>(f: F, a: f64, b: f64, fa: f64, fb: f64) -> f64 {
+    let positive = fb > 0.0;
+    let (mut a, mut b, mut fa, mut fb) = (a, b, fa, fb);
+    let (mut c, mut fc) = (b, fb);
+    let mut d = b - a;
+    let mut e = d;
+    for _ in 0..MAX_ITERATIONS {
+        if (fb > 0.0) == (fc > 0.0) {
+            // Keep the root between b and c.
+            c = a;
+            fc = fa;
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            a = b;
+            b = c;
+            c = a;
+            fa = fb;
+            fb = fc;
+            fc = fa;
+        }
+        let tol = 2.0 * f64::EPSILON * b.abs();
+        let m = 0.5 * (c - b);
+        if m.abs() <= tol || fb == 0.0 {
+            break;
+        }
+        if e.abs() >= tol && fa.abs() > fb.abs() {
+            // Secant or inverse quadratic interpolation.
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2.0 * m * s, 1.0 - s)
+            } else {
+                let q = fa / fc;
+                let r = fb / fc;
+                (
+                    s * (2.0 * m * q * (q - r) - (b - a) * (r - 1.0)),
+                    (q - 1.0) * (r - 1.0) * (s - 1.0),
+                )
+            };
+            if p > 0.0 {
+                q = -q;
+            }
+            p = p.abs();
+            if 2.0 * p < (3.0 * m * q - (tol * q).abs()).min((e * q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = m;
+                e = d;
+            }
+        } else {
+            d = m;
+            e = d;
+        }
+        a = b;
+        fa = fb;
+        b += if d.abs() > tol { d } else { tol.copysign(m) };
+        fb = f(b);
+    }
+    return if fb == 0.0 || (fb > 0.0) == positive {
+        b
+    } else {
+        c
+    };
+}
@@ -0,0 +1,163 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::util;
+use crate::Vec3;
+
+/// A static background potential that the bodies feel but do not source,
+/// such as the galaxy around a star cluster. All are centered on the origin.
+///
+/// The potentials of a state are summed, so a galaxy can be built from a
+/// disk, a bulge and a halo. The kernels whose accelerations depend on the
+/// positions alone add them to the pairwise forces, except for the Hermite,
+/// Wisdom–Holman and chain and time transformed kernels, which are built
+/// around the pairwise forces. The potential energy includes them, so that
+/// the total energy is still conserved.
+pub trait ExternalPotential: Debug + Send + Sync {
+    /// Acceleration at `p`.
+    fn acceleration(&self, p: &Vec3) -> Vec3;
+
+    /// Potential per unit mass at `p`.
+    fn potential(&self, p: &Vec3) -> f64;
+}
+
+/// Sum of the accelerations of `external` at `p`.
+#[inline(always)]
+pub fn external_acceleration(external: &[Arc<dyn ExternalPotential>], p: &Vec3) -> Vec3 {
+    let mut a = Vec3::ZERO;
+    for potential in external {
+        a += potential.acceleration(p);
+    }
+    return a;
+}
+
+/// Point mass `m`, `-G m / r`.
+#[derive(Clone, Copy, Debug)]
+pub struct PointMassPotential {
+    pub m: f64,
+}
+
+impl ExternalPotential for PointMassPotential {
+    fn acceleration(&self, p: &Vec3) -> Vec3 {
+        return Vec3::calc_r(p, &Vec3::ZERO) * (util::GRAVITY_CONSTANT * self.m);
+    }
+
+    fn potential(&self, p: &Vec3) -> f64 {
+        return -util::GRAVITY_CONSTANT * self.m / p.norm();
+    }
+}
+
+/// Plummer sphere of mass `m` and scale radius `b`, `-G m / sqrt(r^2 + b^2)`,
+/// for bulges and small galaxies.
+#[derive(Clone, Copy, Debug)]
+pub struct PlummerPotential {
+    pub m: f64,
+    pub b: f64,
+}
+
+impl ExternalPotential for PlummerPotential {
+    fn acceleration(&self, p: &Vec3) -> Vec3 {
+        let s2 = p.norm_squared() + self.b * self.b;
+        return *p * (-util::GRAVITY_CONSTANT * self.m / (s2 * s2.sqrt()));
+    }
+
+    fn potential(&self, p: &Vec3) -> f64 {
+        return -util::GRAVITY_CONSTANT * self.m / (p.norm_squared() + self.b * self.b).sqrt();
+    }
+}
+
+/// Miyamoto & Nagai (1975) disk of mass `m`, scale length `a` and scale
+/// height `b` in the xy plane,
+/// `-G m / sqrt(R^2 + (a + sqrt(z^2 + b^2))^2)`.
+#[derive(Clone, Copy, Debug)]
+pub struct MiyamotoNagaiPotential {
+    pub m: f64,
+    pub a: f64,
+    pub b: f64,
+}
+
+impl ExternalPotential for MiyamotoNagaiPotential {
+    fn acceleration(&self, p: &Vec3) -> Vec3 {
+        let [x, y, z] = <[f64; 3]>::from(*p);
+        let zeta = (z * z + self.b * self.b).sqrt();
+        let az = self.a + zeta;
+        let d2 = x * x + y * y + az * az;
+        let k = -util::GRAVITY_CONSTANT * self.m / (d2 * d2.sqrt());
+        return Vec3::new(k * x, k * y, k * z * az / zeta);
+    }
+
+    fn potential(&self, p: &Vec3) -> f64 {
+        let [x, y, z] = <[f64; 3]>::from(*p);
+        let az = self.a + (z * z + self.b * self.b).sqrt();
+        return -util::GRAVITY_CONSTANT * self.m / (x * x + y * y + az * az).sqrt();
+    }
+}
+
+/// Navarro, Frenk & White (1996) halo with scale radius `r_s` and
+/// characteristic mass `m = 4 pi rho_0 r_s^3`, `-G m ln(1 + r / r_s) / r`.
+/// The enclosed mass grows without bound, so the halo should be cut off well
+/// outside the region of interest by the choice of `m` and `r_s` alone.
+#[derive(Clone, Copy, Debug)]
+pub struct NfwPotential {
+    pub m: f64,
+    pub r_s: f64,
+}
+
+impl ExternalPotential for NfwPotential {
+    fn acceleration(&self, p: &Vec3) -> Vec3 {
+        let r = p.norm();
+        if r == 0.0 {
+            return Vec3::ZERO;
+        }
+        // G M(<r) / r^2, with M(<r) = m (ln(1 + x) - x / (1 + x)).
+        let x = r / self.r_s;
+        let enclosed = self.m * (x.ln_1p() - x / (1.0 + x));
+        return *p * (-util::GRAVITY_CONSTANT * enclosed / (r * r * r));
+    }
+
+    fn potential(&self, p: &Vec3) -> f64 {
+        let r = p.norm();
+        if r == 0.0 {
+            return -util::GRAVITY_CONSTANT * self.m / self.r_s;
+        }
+        return -util::GRAVITY_CONSTANT * self.m * (r / self.r_s).ln_1p() / r;
+    }
+}
+
+/// Linear tidal field `a = -T p` with the symmetric tidal tensor `T`,
+/// `p^T T p / 2`, the leading term of a distant potential around the origin.
+/// It is fixed in space, so it describes a host whose direction does not
+/// change over the run.
+#[derive(Clone, Copy, Debug)]
+pub struct TidalField {
+    pub tensor: [[f64; 3]; 3],
+}
+
+impl TidalField {
+    /// Tidal field of a point mass `m` at distance `d` along the x axis,
+    /// stretching along x and compressing across.
+    pub fn point_mass(m: f64, d: f64) -> Self {
+        let k = util::GRAVITY_CONSTANT * m / (d * d * d);
+        TidalField {
+            tensor: [[-2.0 * k, 0.0, 0.0], [0.0, k, 0.0], [0.0, 0.0, k]],
+        }
+    }
+}
+
+impl ExternalPotential for TidalField {
+    fn acceleration(&self, p: &Vec3) -> Vec3 {
+        let x = <[f64; 3]>::from(*p);
+        let t = &self.tensor;
+        let mut a = [0.0; 3];
+        for i in 0..3 {
+            a[i] = -(t[i][0] * x[0] + t[i][1] * x[1] + t[i][2] * x[2]);
+        }
+        return Vec3::new(a[0], a[1], a[2]);
+    }
+
+    fn potential(&self, p: &Vec3) -> f64 {
+        let a = <[f64; 3]>::from(self.acceleration(p));
+        let x = <[f64; 3]>::from(*p);
+        return -(a[0] * x[0] + a[1] * x[1] + a[2] * x[2]) / 2.0;
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt::Debug;
+
+use crate::util;
+use crate::Vec3;
+
+/// A central pairwise interaction in place of Newtonian gravity.
+///
+/// The kernels built on a generic acceleration, the three-body ones except
+/// the chain and time transformed kernels, and the N-body Velocity Verlet,
+/// Yoshida4 and RK4 kernels, sum it directly whatever their force solver.
+/// The regularized, Wisdom–Holman and Hermite kernels are built around
+/// Newtonian gravity. The softening only applies to Newtonian gravity, and
+/// the potential energy uses `potential`.
+pub trait ForceLaw: Debug + Send + Sync {
+    /// For bodies `i` and `j` at squared separation `r2`, with `mu` the masses
+    /// times `G`, the factors `f_i` and `f_j` such that
+    /// `a_i = (p_j - p_i) f_i` and `a_j = (p_i - p_j) f_j`, both positive for
+    /// attraction. Newtonian gravity is `(mu_j / r^3, mu_i / r^3)`.
+    fn acceleration(&self, mu: &[f64], i: usize, j: usize, r2: f64) -> (f64, f64);
+
+    /// Potential energy of the pair, whose derivative with respect to the
+    /// separation is the attractive force.
+    fn potential(&self, mu: &[f64], i: usize, j: usize, r2: f64) -> f64;
+}
+
+/// Accelerations from `force_law` by direct summation, times `scale`. Only
+/// the first `n_massive` bodies act, the rest being test particles.
+pub fn calc_a_force_law(
+    p: &[Vec3],
+    mu: &[f64],
+    n_massive: usize,
+    force_law: &dyn ForceLaw,
+    scale: f64,
+    a: &mut [Vec3],
+) {
+    a.fill(Vec3::ZERO);
+    for i in 0..n_massive {
+        for j in (i + 1)..p.len() {
+            let r = p[j] - p[i];
+            let (fi, fj) = force_law.acceleration(mu, i, j, r.norm_squared());
+            a[i] = Vec3::mul_add(r, Vec3::splat(fi * scale), a[i]);
+            a[j] = Vec3::mul_neg_add(r, Vec3::splat(fj * scale), a[j]);
+        }
+    }
+}
+
+/// Electrostatics between the signed charges `q` in C, indexed like the
+/// bodies, with the masses only giving the inertia, so every charged body
+/// needs a mass. Gravity is left out, being negligible next to it for any
+/// charged particles.
+#[derive(Clone, Debug)]
+pub struct Coulomb {
+    pub q: Vec<f64>,
+}
+
+impl ForceLaw for Coulomb {
+    fn acceleration(&self, mu: &[f64], i: usize, j: usize, r2: f64) -> (f64, f64) {
+        // Like charges repel, and a = F / m = F G / mu.
+        let f = -util::COULOMB_CONSTANT * self.q[i] * self.q[j] * util::GRAVITY_CONSTANT
+            / (r2 * r2.sqrt());
+        if f == 0.0 {
+            // Neutral bodies feel nothing, even massless ones.
+            return (0.0, 0.0);
+        }
+        assert!(
+            mu[i] > 0.0 && mu[j] > 0.0,
+            "Coulomb needs a mass for every charged body"
+        );
+        return (f / mu[i], f / mu[j]);
+    }
+
+    fn potential(&self, _mu: &[f64], i: usize, j: usize, r2: f64) -> f64 {
+        return util::COULOMB_CONSTANT * self.q[i] * self.q[j] / r2.sqrt();
+    }
+}
+
+/// Gravity screened beyond the length `lambda`, `-G m e^(-r / lambda) / r`.
+#[derive(Clone, Copy, Debug)]
+pub struct Yukawa {
+    pub lambda: f64,
+}
+
+impl ForceLaw for Yukawa {
+    fn acceleration(&self, mu: &[f64], i: usize, j: usize, r2: f64) -> (f64, f64) {
+        let r = r2.sqrt();
+        let x = r / self.lambda;
+        let g = (-x).exp() * (1.0 + x) / (r2 * r);
+        return (mu[j] * g, mu[i] * g);
+    }
+
+    fn potential(&self, mu: &[f64], i: usize, j: usize, r2: f64) -> f64 {
+        let r = r2.sqrt();
+        return -mu[i] * mu[j] * (-r / self.lambda).exp() / (util::GRAVITY_CONSTANT * r);
+    }
+}
+
+/// Attraction `G m_i m_j r0^(n - 2) / r^n`, equal to Newtonian gravity at
+/// `r0`, and Newtonian gravity for `n = 2`.
+#[derive(Clone, Copy, Debug)]
+pub struct PowerLaw {
+    pub n: f64,
+    pub r0: f64,
+}
+
+impl ForceLaw for PowerLaw {
+    fn acceleration(&self, mu: &[f64], i: usize, j: usize, r2: f64) -> (f64, f64) {
+        let g = self.r0.powf(self.n - 2.0) * r2.powf(-(self.n + 1.0) / 2.0);
+        return (mu[j] * g, mu[i] * g);
+    }
+
+    fn potential(&self, mu: &[f64], i: usize, j: usize, r2: f64) -> f64 {
+        let k = mu[i] * mu[j] / util::GRAVITY_CONSTANT;
+        let r = r2.sqrt();
+        if self.n == 1.0 {
+            return k / self.r0 * (r / self.r0).ln();
+        }
+        return -k * (self.r0 / r).powf(self.n - 2.0) / ((self.n - 1.0) * r);
+    }
+}
+
+/// MOND-like gravity with the acceleration scale `a0`, the Newtonian
+/// attraction of each pair scaled by the simple interpolating function
+/// `nu(y) = 1/2 + sqrt(1/4 + 1/y)` of `y = G (m_i + m_j) / (r^2 a0)`. Far
+/// beyond `sqrt(G m / a0)` a test particle feels `sqrt(G m a0) / r`, which
+/// gives flat rotation curves. Interpolating pair by pair is a toy model: it
+/// keeps momentum and energy conserved, but is not a solution of a MOND field
+/// equation, so the external field effect and the nonlinear superposition
+/// are missing.
+#[derive(Clone, Copy, Debug)]
+pub struct Mond {
+    pub a0: f64,
+}
+
+impl Mond {
+    /// Acceleration scale fitted to galaxy rotation curves, in m/s^2.
+    pub const A0: f64 = 1.2e-10;
+}
+
+impl ForceLaw for Mond {
+    fn acceleration(&self, mu: &[f64], i: usize, j: usize, r2: f64) -> (f64, f64) {
+        let nu = 0.5 + (0.25 + self.a0 * r2 / (mu[i] + mu[j])).sqrt();
+        let g = nu / (r2 * r2.sqrt());
+        return (mu[j] * g, mu[i] * g);
+    }
+
+    fn potential(&self, mu: &[f64], i: usize, j: usize, r2: f64) -> f64 {
+        // Integral of the force from the Newtonian limit at r = 0, growing
+        // like a logarithm at large r.
+        let k = mu[i] * mu[j] / util::GRAVITY_CONSTANT;
+        let b = self.a0 / (mu[i] + mu[j]);
+        let r = r2.sqrt();
+        let sb = b.sqrt();
+        return k * (-0.5 / r - (0.25 + b * r2).sqrt() / r + sb * (2.0 * sb * r).asinh());
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{util, Scalar, Vec3, Vector};
+
+use std::sync::Arc;
+
+use super::three_body::natural_scales;
+use super::{
+    calc_a_force_law, external_acceleration, Extension, ExternalPotential, ForceLaw, PhysicsState,
+    PostNewtonian, Softening,
+};
+
+mod barnes_hut;
+mod collision;
+mod fmm;
+mod hermite;
+mod ks_regularized;
+mod octree;
+mod rk4;
+mod simd;
+mod vel_verlet;
+mod wisdom_holman;
+mod yoshida4;
+
+pub use barnes_hut::*;
+pub use collision::*;
+pub use fmm::*;
+pub use hermite::*;
+pub use ks_regularized::*;
+pub use octree::*;
+pub use rk4::*;
+pub use simd::*;
+pub use vel_verlet::*;
+pub use wisdom_holman::*;
+pub use yoshida4::*;
+
+pub trait NBodyKernel {
+    #[must_use]
+    fn kernel(&self, state: PhysicsState, steps: u64, dt: u64) -> PhysicsState;
+
+    fn simulate(&self, state: &mut PhysicsState, batch_count: u64, step_count: u64, dt: u64) {
+        let mut state1 = state.clone();
+        for _ in 0..batch_count {
+            state1 = self.kernel(state1, step_count, dt);
+        }
+        *state = state1;
+    }
+}
+
+/// Computes gravitational accelerations for the N-body kernels.
+///
+/// `m` holds the masses premultiplied by whatever constant the kernel wants
+/// folded into the accelerations (`G`, or `G * dt^2` for the kernels that
+/// work with scaled velocities), as in `three_body::calc_a`. A force law in the
+/// state takes the place of the solver, summed directly. Solvers are for
+/// `Vec3` unless they are generic over the precision `V`, as is direct
+/// summation.
+pub trait ForceSolver<V: Vector = Vec3> {
+    fn calc_a(&self, p: &[V], m: &[V::Scalar], softening: &Softening, a: &mut [V]);
+
+    /// Accelerations of the test particles at `p_test` from the bodies at `p`,
+    /// O(N_massive * N_test) by direct summation unless the solver has
+    /// something faster.
+    fn calc_a_test(
+        &self,
+        p: &[V],
+        m: &[V::Scalar],
+        softening: &Softening,
+        p_test: &[V],
+        a_test: &mut [V],
+    ) {
+        calc_a_test(p, m, softening, p_test, a_test);
+    }
+}
+
+/// Exact O(N^2) pairwise summation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DirectSummation;
+
+impl<V: Vector> ForceSolver<V> for DirectSummation {
+    #[inline]
+    fn calc_a(&self, p: &[V], m: &[V::Scalar], softening: &Softening, a: &mut [V]) {
+        calc_a(p, m, softening, a);
+    }
+}
+
+#[inline]
+pub fn calc_a<V: Vector>(p: &[V], m: &[V::Scalar], softening: &Softening, a: &mut [V]) {
+    a.fill(V::ZERO);
+    for i in 0..p.len() {
+        let mi = V::splat(m[i]);
+        for j in (i + 1)..p.len() {
+            let r = softening.calc_r(&p[i], &p[j]);
+            a[i] = V::mul_add(r, V::splat(m[j]), a[i]);
+            a[j] = V::mul_neg_add(r, mi, a[j]);
+        }
+    }
+}
+
+#[inline]
+pub fn calc_a_test<V: Vector>(
+    p: &[V],
+    m: &[V::Scalar],
+    softening: &Softening,
+    p_test: &[V],
+    a_test: &mut [V],
+) {
+    for i in 0..p_test.len() {
+        let mut ai = V::ZERO;
+        for j in 0..p.len() {
+            let r = softening.calc_r(&p_test[i], &p[j]);
+            ai = V::mul_add(r, V::splat(m[j]), ai);
+        }
+        a_test[i] = ai;
+    }
+}
+
+/// Accelerations of the massive bodies, the first `m.len()` in `p`, by
+/// `solver`, and of the test particles after them from the massive bodies
+/// alone.
+#[inline(always)]
+fn calc_a_massive<V: Vector, F: ForceSolver<V>>(
+    solver: &F,
+    p: &[V],
+    m: &[V::Scalar],
+    softening: &Softening,
+    a: &mut [V],
+) {
+    let (p, p_test) = p.split_at(m.len());
+    let (a, a_test) = a.split_at_mut(m.len());
+    solver.calc_a(p, m, softening, a);
+    if !p_test.is_empty() {
+        solver.calc_a_test(p, m, softening, p_test, a_test);
+    }
+}
+
+/// `calc_a_force_law` for the positions `p` in units of `length` at the
+/// precision of `V`, evaluated in SI units and `f64`.
+fn calc_a_force_law_in_units<V: Vector>(
+    p: &[V],
+    length: f64,
+    mu: &[f64],
+    n_massive: usize,
+    force_law: &dyn ForceLaw,
+    scale: f64,
+    a: &mut [V],
+) {
+    let p: Vec<Vec3> = p.iter().map(|p| p.to_vec3() * length).collect();
+    let mut a_si = vec![Vec3::ZERO; p.len()];
+    calc_a_force_law(&p, mu, n_massive, force_law, scale, &mut a_si);
+    for i in 0..a.len() {
+        a[i] = V::from_vec3(a_si[i]);
+    }
+}
+
+/// Adds the accelerations of the external potentials times `scale`, for the
+/// kernels folding a constant into the pairwise accelerations as in `m`, at
+/// the positions `p` in units of `length`.
+#[inline(always)]
+fn add_external<V: Vector>(
+    p: &[V],
+    length: f64,
+    external: &[Arc<dyn ExternalPotential>],
+    scale: f64,
+    a: &mut [V],
+) {
+    if external.is_empty() {
+        return;
+    }
+    let scale = V::splat(V::Scalar::from_f64(scale));
+    for i in 0..p.len() {
+        let a_i = external_acceleration(external, &(p[i].to_vec3() * length));
+        a[i] = V::mul_add(V::from_vec3(a_i), scale, a[i]);
+    }
+}
+
+/// Adds the post-Newtonian corrections times `scale` to the Newtonian
+/// accelerations `a`, for the positions `p` and velocities `v` in the natural
+/// `units` of `natural_scales`, evaluated in SI units and `f64`.
+fn add_post_newtonian<V: Vector>(
+    p: &[V],
+    v: &[V],
+    units: (f64, f64),
+    post_newtonian: &PostNewtonian,
+    mu: &[f64],
+    scale: f64,
+    a: &mut [V],
+) {
+    let (length, time) = units;
+    let p: Vec<Vec3> = p.iter().map(|p| p.to_vec3() * length).collect();
+    let v: Vec<Vec3> = v.iter().map(|v| v.to_vec3() * (length / time)).collect();
+    let mut a_si: Vec<Vec3> = a.iter().map(|a| a.to_vec3() / scale).collect();
+    post_newtonian.add_a(&p, &v, mu, &mut a_si);
+    for i in 0..a.len() {
+        a[i] = V::from_vec3(a_si[i] * scale);
+    }
+}
+
+/// `natural_scales` of a state, for the kernels generic over precision.
+fn units(state: &PhysicsState) -> (f64, f64) {
+    return natural_scales(
+        &state.p,
+        &scale_m(&state.m[..state.n_massive()], util::GRAVITY_CONSTANT),
+    );
+}
+
+/// Positions or velocities at the precision of `V`, in units of `unit`.
+fn to_units<V: Vector>(x: &[Vec3], unit: f64) -> Vec<V> {
+    return x.iter().map(|&x| V::from_vec3(x / unit)).collect();
+}
+
+/// Back from `to_units`.
+fn from_units<V: Vector>(x: &[V], unit: f64) -> Vec<Vec3> {
+    return x.iter().map(|x| x.to_vec3() * unit).collect();
+}
+
+#[inline(always)]
+fn scale_m(m: &[f64], factor: f64) -> Vec<f64> {
+    m.iter().map(|&m| m * factor).collect()
+}
+
+#[inline(always)]
+fn add<V: Vector>(a: &mut [V], b: &[V]) {
+    for i in 0..a.len() {
+        a[i] = a[i] + b[i];
+    }
+}
+
+#[inline(always)]
+fn scale<V: Vector>(a: &mut [V], c: V::Scalar) {
+    for x in a.iter_mut() {
+        *x = *x * c;
+    }
+}
+
+#[inline(always)]
+fn advance<V: Vector>(a: &mut [V], b: &[V], c: &V) {
+    for i in 0..a.len() {
+        a[i] = V::mul_add(b[i], *c, a[i]);
+    }
+}
+
+/// Stumpff functions `c_0` to `c_3` of `z`.
+fn stumpff(z: f64) -> [f64; 4] {
+    if z.abs() < 1.0 {
+        // c_k(z) = sum (-z)^j / (2j + k)!, the closed forms below cancel badly.
+        let mut c = [0.0f64; 4];
+        for k in 0..4 {
+            let mut term = 1.0;
+            for i in 1..=k {
+                term /= i as f64;
+            }
+            let mut j = 0;
+            while term.abs() > f64::EPSILON * c[k].abs() || j == 0 {
+                c[k] += term;
+                j += 1;
+                term *= -z / ((2 * j + k - 1) * (2 * j + k)) as f64;
+            }
+        }
+        return c;
+    }
+    let (c0, c1) = if z > 0.0 {
+        let s = z.sqrt();
+        (s.cos(), s.sin() / s)
+    } else {
+        let s = (-z).sqrt();
+        (s.cosh(), s.sinh() / s)
+    };
+    return [c0, c1, (1.0 - c0) / z, (1.0 - c1) / z];
+}
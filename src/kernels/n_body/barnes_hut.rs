@@ -0,0 +1,101 @@
+use crate::Vec3;
+
+use super::*;
+
+/// Barnes–Hut tree code with monopole cells.
+///
+/// A cell of width `s` whose center of mass is at distance `d` from the body
+/// is accepted when `d > s / theta + delta`, where `delta` is the offset of the
+/// center of mass from the geometric center of the cell (Barnes 1994). Smaller
+/// `theta` is more accurate; `theta = 0` degenerates to direct summation.
+/// A cell containing the body itself is always opened, which matters for
+/// `theta` above about 1, where such a cell can pass the test. Softening
+/// applies to cells as well as bodies.
+#[derive(Clone, Copy, Debug)]
+pub struct BarnesHut {
+    pub theta: f64,
+    pub leaf_size: usize,
+}
+
+impl BarnesHut {
+    pub fn new(theta: f64) -> Self {
+        BarnesHut {
+            theta,
+            leaf_size: 8,
+        }
+    }
+}
+
+impl Default for BarnesHut {
+    fn default() -> Self {
+        BarnesHut::new(0.5)
+    }
+}
+
+struct Cell {
+    com: Vec3,
+    mass: f64,
+    // Squared opening radius, infinite for cells that must always be opened.
+    r_open2: f64,
+}
+
+impl ForceSolver for BarnesHut {
+    fn calc_a(&self, p: &[Vec3], m: &[f64], softening: &Softening, a: &mut [Vec3]) {
+        let tree = Octree::new(p, self.leaf_size);
+
+        let mut cells: Vec<Cell> = Vec::with_capacity(tree.nodes.len());
+        for node in &tree.nodes {
+            let mut com = Vec3::ZERO;
+            let mut mass = 0.0;
+            for &i in &tree.order[node.start..node.end] {
+                com = Vec3::mul_add(p[i], Vec3::splat(m[i]), com);
+                mass += m[i];
+            }
+            com = if mass != 0.0 { com / mass } else { node.center };
+            let r_open = if self.theta > 0.0 {
+                2.0 * node.half_width / self.theta + (com - node.center).norm()
+            } else {
+                f64::INFINITY
+            };
+            cells.push(Cell {
+                com,
+                mass,
+                r_open2: r_open * r_open,
+            });
+        }
+
+        // Position of each body in the tree order, to tell the cells holding it.
+        let mut rank = vec![0; p.len()];
+        for (k, &i) in tree.order.iter().enumerate() {
+            rank[i] = k;
+        }
+
+        let mut stack: Vec<usize> = Vec::with_capacity(64);
+        for i in 0..p.len() {
+            let mut ai = Vec3::ZERO;
+            stack.push(0);
+            while let Some(k) = stack.pop() {
+                let node = &tree.nodes[k];
+                let cell = &cells[k];
+                if cell.mass == 0.0 {
+                    continue;
+                }
+                let contains_i = node.start <= rank[i] && rank[i] < node.end;
+                if !contains_i && (cell.com - p[i]).norm_squared() > cell.r_open2 {
+                    let r = softening.calc_r(&p[i], &cell.com);
+                    ai = Vec3::mul_add(r, Vec3::splat(cell.mass), ai);
+                } else if node.is_leaf() {
+                    for &j in &tree.order[node.start..node.end] {
+                        if j != i {
+                            let r = softening.calc_r(&p[i], &p[j]);
+                            ai = Vec3::mul_add(r, Vec3::splat(m[j]), ai);
+                        }
+                    }
+                } else {
+                    stack.extend(node.children());
+                }
+            }
+            a[i] = ai;
+        }
+    }
+}
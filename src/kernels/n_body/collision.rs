@@ -0,0 +1,212 @@
+use crate::Vec3;
+
+use super::*;
+
+/// What happens when two bodies touch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CollisionOutcome {
+    /// The bodies merge into one at their center of mass, conserving mass and
+    /// momentum, with the volume of both.
+    Merge,
+    /// The bodies bounce as hard spheres, reversing the normal component of
+    /// their relative velocity scaled by `restitution`, 1 for an elastic
+    /// bounce.
+    Bounce { restitution: f64 },
+}
+
+/// A collision, with the bodies indexed as at the time of contact.
+#[derive(Clone, Copy, Debug)]
+pub struct Collision {
+    pub t: u64,
+    pub pair: (usize, usize),
+    /// Center of mass of the pair at contact.
+    pub p: Vec3,
+    /// Relative speed along the line of centers at contact.
+    pub speed: f64,
+}
+
+/// Runs `kernel` one step at a time and handles collisions between bodies
+/// with nonzero radius in between.
+///
+/// Positions are interpolated linearly over each step, and a pair collides
+/// when the interpolated separation falls to the sum of the radii, so fast
+/// bodies cannot pass through each other between the ends of a step as long
+/// as their paths are close to straight over it. The kernel is then run again
+/// from the start of the step up to the earliest contact, the outcome is
+/// applied to the state there, and the rest of the step is run from it, so
+/// a step can be split at any number of collisions. The split parts are run
+/// in power-of-two sub-steps, since the Hermite kernel's block steps only
+/// divide a step down to its lowest set bit. Each call of the kernel starts
+/// afresh, so the KS kernel re-picks its regularized pairs at every split.
+/// Merging shrinks the state, so the kernel must not keep anything per body
+/// between calls.
+///
+/// Overlapping pairs only bounce while they approach, so a pair that still
+/// overlaps after bouncing separates instead of bouncing back and forth.
+#[derive(Clone, Copy, Debug)]
+pub struct CollisionKernel<K: NBodyKernel> {
+    pub kernel: K,
+    pub outcome: CollisionOutcome,
+}
+
+impl<K: NBodyKernel> CollisionKernel<K> {
+    pub fn new(kernel: K, outcome: CollisionOutcome) -> Self {
+        CollisionKernel {
+            kernel: kernel,
+            outcome: outcome,
+        }
+    }
+
+    /// Fraction of the step at which `i` and `j` touch, if they do within it,
+    /// from the positions `p0` at the start and `p` at the end.
+    fn contact(p0: &[Vec3], p: &[Vec3], r: &[f64], i: usize, j: usize) -> Option<f64> {
+        let radius = r[i] + r[j];
+        if radius <= 0.0 {
+            return None;
+        }
+        let d0 = p0[j] - p0[i];
+        let e = (p[j] - p[i]) - d0;
+        // |d0 + s e|^2 = radius^2 as a s^2 + 2 b s + c = 0.
+        let a = e.norm_squared();
+        let b = (d0 * e).reduce_add();
+        let c = d0.norm_squared() - radius * radius;
+        if c <= 0.0 {
+            return Some(0.0);
+        }
+        if b >= 0.0 {
+            return None;
+        }
+        let disc = b * b - a * c;
+        if disc < 0.0 {
+            return None;
+        }
+        // Smaller root, in the cancellation free form.
+        let s = c / (-b + disc.sqrt());
+        if s <= 1.0 {
+            return Some(s);
+        }
+        return None;
+    }
+
+    /// Runs the kernel for `dt` ticks in power-of-two steps, longest first.
+    fn advance(&self, state: PhysicsState, dt: u64) -> PhysicsState {
+        let mut state = state;
+        let mut left = dt;
+        while left > 0 {
+            let step = 1u64 << (63 - left.leading_zeros());
+            state = self.kernel.kernel(state, 1, step);
+            left -= step;
+        }
+        return state;
+    }
+
+    /// Applies the outcome to `i` and `j` in `state` at their contact, unless
+    /// they are to bounce but already separate.
+    fn collide(&self, state: &mut PhysicsState, i: usize, j: usize) -> Option<Collision> {
+        let (mi, mj) = (state.m[i], state.m[j]);
+        let mt = mi + mj;
+        // Mass fractions, even for two massless bodies.
+        let (fi, fj) = if mt > 0.0 {
+            (mi / mt, mj / mt)
+        } else {
+            (0.5, 0.5)
+        };
+        let d = state.p[j] - state.p[i];
+        let dn = d.norm();
+        // Coincident centers have no line of centers to bounce along.
+        let normal = if dn > 0.0 { d / dn } else { Vec3::ZERO };
+        let w = state.v[j] - state.v[i];
+        let speed = (w * normal).reduce_add();
+        let com = fi * state.p[i] + fj * state.p[j];
+
+        match self.outcome {
+            CollisionOutcome::Merge => {
+                state.v[i] = fi * state.v[i] + fj * state.v[j];
+                state.p[i] = com;
+                state.m[i] = mt;
+                state.r[i] = (state.r[i].powi(3) + state.r[j].powi(3)).cbrt();
+                // A merger keeps the lower index, so massive bodies stay
+                // massive and only test particles can leave the tail.
+                if j >= state.n_massive() {
+                    state.n_test -= 1;
+                }
+                state.p.remove(j);
+                state.v.remove(j);
+                state.m.remove(j);
+                state.r.remove(j);
+            }
+            CollisionOutcome::Bounce { restitution } => {
+                if speed >= 0.0 {
+                    return None;
+                }
+                let dv = normal * ((1.0 + restitution) * speed);
+                state.v[i] = Vec3::mul_add(dv, Vec3::splat(fj), state.v[i]);
+                state.v[j] = Vec3::mul_neg_add(dv, Vec3::splat(fi), state.v[j]);
+            }
+        }
+        return Some(Collision {
+            t: state.t,
+            pair: (i, j),
+            p: com,
+            speed: speed,
+        });
+    }
+
+    /// Like `kernel`, but also returns every collision.
+    pub fn kernel_logged(
+        &self,
+        state: PhysicsState,
+        steps: u64,
+        dt: u64,
+    ) -> (PhysicsState, Vec<Collision>) {
+        let mut log = vec![];
+        let mut state = state;
+        for _ in 0..steps {
+            let mut left = dt;
+            while left > 0 {
+                let end = self.advance(state.clone(), left);
+                let n = end.p.len();
+
+                let mut contacts = vec![];
+                for i in 0..n {
+                    for j in (i + 1)..n {
+                        if let Some(s) = Self::contact(&state.p, &end.p, &end.r, i, j) {
+                            contacts.push((s, i, j));
+                        }
+                    }
+                }
+                contacts.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+                let mut collided = None;
+                for (s, i, j) in contacts {
+                    // Rounded down, so the pair is rewound to just before the
+                    // contact rather than past it.
+                    let dt_contact = (s * left as f64) as u64;
+                    let mut contact = self.advance(state.clone(), dt_contact);
+                    if let Some(collision) = self.collide(&mut contact, i, j) {
+                        collided = Some((contact, dt_contact, collision));
+                        break;
+                    }
+                }
+                match collided {
+                    Some((contact, dt_contact, collision)) => {
+                        log.push(collision);
+                        state = contact;
+                        left -= dt_contact;
+                    }
+                    None => {
+                        state = end;
+                        left = 0;
+                    }
+                }
+            }
+        }
+        return (state, log);
+    }
+}
+
+impl<K: NBodyKernel> NBodyKernel for CollisionKernel<K> {
+    fn kernel(&self, state: PhysicsState, steps: u64, dt: u64) -> PhysicsState {
+        self.kernel_logged(state, steps, dt).0
+    }
+}
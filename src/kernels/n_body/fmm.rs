@@ -0,0 +1,339 @@
+use crate::Vec3;
+
+use super::*;
+
+/// Cartesian fast multipole method of arbitrary expansion order.
+///
+/// Multipoles are the raw moments `M_k = sum m (x - c)^k` of each cell about
+/// its geometric center, and local expansions are Taylor series of the
+/// potential `L_n y^n`, both truncated at total degree `order`. The M2L
+/// operator uses the Taylor coefficients `a_k = D^k (1/r) / k!`, generated
+/// with the recurrence
+///
+/// `|k| r^2 a_k = -(2|k| - 1) sum_i r_i a_{k-e_i} - (|k| - 1) sum_i a_{k-2e_i}`.
+///
+/// Cells are paired with a mutual dual tree walk and interact through their
+/// expansions when `theta * |c_a - c_b| > r_a + r_b`, `r` being the largest
+/// distance of a body in the cell from its center; everything else is summed
+/// directly. Plummer softening is expanded along with the potential, since
+/// `1 / sqrt(r^2 + eps^2)` obeys the same recurrence with `r^2 + eps^2` in
+/// place of `r^2`. The spline kernel is Newtonian beyond its support, so with
+/// it cells only interact through their expansions when no two of their
+/// bodies can be closer than that.
+pub struct FastMultipole {
+    pub order: usize,
+    pub theta: f64,
+    pub leaf_size: usize,
+    terms: Vec<[usize; 3]>,
+    index: Vec<usize>,
+    // Index of k - e_axis and the axis, for every term except the constant.
+    parent: Vec<(usize, usize)>,
+    // For every k: (l, k - l, C(k, l)) over all l <= k.
+    shift_up: Vec<Vec<(usize, usize, f64)>>,
+    // For every m: (n, n - m, C(n, m)) over all n >= m.
+    shift_down: Vec<Vec<(usize, usize, f64)>>,
+    // For every n: (k, n + k, (-1)^|k| C(n + k, n), (-1)^|n| C(n + k, n)) over
+    // |n| + |k| <= order. The last factor also turns a_{n+k}(r) into
+    // a_{n+k}(-r) for the reverse M2L.
+    m2l: Vec<Vec<(u32, u32, f64, f64)>>,
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    let mut c = 1.0;
+    for i in 0..k {
+        c = c * (n - i) as f64 / (i + 1) as f64;
+    }
+    return c;
+}
+
+impl FastMultipole {
+    pub fn new(order: usize, theta: f64) -> Self {
+        let dim = order + 1;
+        let mut terms = vec![];
+        for degree in 0..=order {
+            for i in (0..=degree).rev() {
+                for j in (0..=(degree - i)).rev() {
+                    terms.push([i, j, degree - i - j]);
+                }
+            }
+        }
+        let mut index = vec![usize::MAX; dim * dim * dim];
+        for (t, k) in terms.iter().enumerate() {
+            index[(k[0] * dim + k[1]) * dim + k[2]] = t;
+        }
+        let lookup = |k: [usize; 3]| index[(k[0] * dim + k[1]) * dim + k[2]];
+
+        let mut parent = vec![(0, 0)];
+        for k in terms.iter().skip(1) {
+            let axis = (0..3).find(|&a| k[a] > 0).unwrap();
+            let mut l = *k;
+            l[axis] -= 1;
+            parent.push((lookup(l), axis));
+        }
+
+        let mut shift_up = vec![vec![]; terms.len()];
+        let mut shift_down = vec![vec![]; terms.len()];
+        for k in &terms {
+            for l in &terms {
+                if (0..3).all(|a| l[a] <= k[a]) {
+                    let c = (0..3).map(|a| binomial(k[a], l[a])).product();
+                    let kl = lookup([k[0] - l[0], k[1] - l[1], k[2] - l[2]]);
+                    shift_up[lookup(*k)].push((lookup(*l), kl, c));
+                    shift_down[lookup(*l)].push((lookup(*k), kl, c));
+                }
+            }
+        }
+
+        let mut m2l = vec![];
+        for n in &terms {
+            let mut list = vec![];
+            for k in &terms {
+                let degree = n.iter().sum::<usize>() + k.iter().sum::<usize>();
+                if degree <= order {
+                    let nk = [n[0] + k[0], n[1] + k[1], n[2] + k[2]];
+                    let sign = |d: usize| if d.is_multiple_of(2) { 1.0 } else { -1.0 };
+                    let c: f64 = (0..3).map(|a| binomial(nk[a], n[a])).product();
+                    let k_sign = sign(k.iter().sum::<usize>());
+                    let n_sign = sign(n.iter().sum::<usize>());
+                    list.push((lookup(*k) as u32, lookup(nk) as u32, k_sign * c, n_sign * c));
+                }
+            }
+            m2l.push(list);
+        }
+
+        FastMultipole {
+            order,
+            theta,
+            leaf_size: 64,
+            terms,
+            index,
+            parent,
+            shift_up,
+            shift_down,
+            m2l,
+        }
+    }
+
+    #[inline]
+    fn lookup(&self, k: [usize; 3]) -> usize {
+        let dim = self.order + 1;
+        self.index[(k[0] * dim + k[1]) * dim + k[2]]
+    }
+
+    /// Fills `out` with all the monomials `x^k`.
+    #[inline]
+    fn powers(&self, x: Vec3, out: &mut [f64]) {
+        let x: [f64; 3] = x.into();
+        out[0] = 1.0;
+        for t in 1..self.terms.len() {
+            let (l, axis) = self.parent[t];
+            out[t] = out[l] * x[axis];
+        }
+    }
+
+    /// Fills `out` with the Taylor coefficients of `1 / sqrt(|r|^2 + eps2)`,
+    /// Plummer softened unless `eps2` is zero.
+    fn taylor_coefficients(&self, r: Vec3, eps2: f64, out: &mut [f64]) {
+        let r2 = r.norm_squared() + eps2;
+        let x: [f64; 3] = r.into();
+        out[0] = 1.0 / r2.sqrt();
+        for t in 1..self.terms.len() {
+            let k = self.terms[t];
+            let degree = (k[0] + k[1] + k[2]) as f64;
+            let mut sum = 0.0;
+            for axis in 0..3 {
+                if k[axis] >= 1 {
+                    let mut l = k;
+                    l[axis] -= 1;
+                    sum += (2.0 * degree - 1.0) * x[axis] * out[self.lookup(l)];
+                    if k[axis] >= 2 {
+                        l[axis] -= 1;
+                        sum += (degree - 1.0) * out[self.lookup(l)];
+                    }
+                }
+            }
+            out[t] = -sum / (degree * r2);
+        }
+    }
+}
+
+impl Default for FastMultipole {
+    fn default() -> Self {
+        FastMultipole::new(6, 0.5)
+    }
+}
+
+fn p2p_self(p: &[Vec3], m: &[f64], softening: &Softening, bodies: &[usize], a: &mut [Vec3]) {
+    for (k, &i) in bodies.iter().enumerate() {
+        let mi = Vec3::splat(m[i]);
+        for &j in &bodies[(k + 1)..] {
+            let r = softening.calc_r(&p[i], &p[j]);
+            a[i] = Vec3::mul_add(r, Vec3::splat(m[j]), a[i]);
+            a[j] = Vec3::mul_neg_add(r, mi, a[j]);
+        }
+    }
+}
+
+fn p2p_mutual(
+    p: &[Vec3],
+    m: &[f64],
+    softening: &Softening,
+    bodies_a: &[usize],
+    bodies_b: &[usize],
+    a: &mut [Vec3],
+) {
+    for &i in bodies_a {
+        let mi = Vec3::splat(m[i]);
+        let mut ai = a[i];
+        for &j in bodies_b {
+            let r = softening.calc_r(&p[i], &p[j]);
+            ai = Vec3::mul_add(r, Vec3::splat(m[j]), ai);
+            a[j] = Vec3::mul_neg_add(r, mi, a[j]);
+        }
+        a[i] = ai;
+    }
+}
+
+impl ForceSolver for FastMultipole {
+    fn calc_a(&self, p: &[Vec3], m: &[f64], softening: &Softening, a: &mut [Vec3]) {
+        a.fill(Vec3::ZERO);
+        if p.is_empty() {
+            return;
+        }
+        let tree = Octree::new(p, self.leaf_size);
+        let nodes = &tree.nodes;
+        let len = self.terms.len();
+        let mut multipole = vec![0.0; nodes.len() * len];
+        let mut local = vec![0.0; nodes.len() * len];
+        let mut pow = vec![0.0; len];
+
+        let (eps2, support) = match *softening {
+            Softening::Plummer(eps) => (eps * eps, 0.0),
+            _ => (0.0, softening.support()),
+        };
+
+        let mut radius = vec![0.0f64; nodes.len()];
+        for (k, node) in nodes.iter().enumerate() {
+            for &i in &tree.order[node.start..node.end] {
+                radius[k] = radius[k].max((p[i] - node.center).norm());
+            }
+        }
+
+        // P2M and M2M, children before parents.
+        for k in (0..nodes.len()).rev() {
+            let node = &nodes[k];
+            // Children always come after their parent, so they live in `tail`.
+            let (head, tail) = multipole.split_at_mut((k + 1) * len);
+            let mk = &mut head[k * len..];
+            if node.is_leaf() {
+                for &i in &tree.order[node.start..node.end] {
+                    self.powers(p[i] - node.center, &mut pow);
+                    for t in 0..len {
+                        mk[t] += m[i] * pow[t];
+                    }
+                }
+            } else {
+                for c in node.children() {
+                    let mc = &tail[(c - k - 1) * len..][..len];
+                    self.powers(nodes[c].center - node.center, &mut pow);
+                    for t in 0..len {
+                        for &(l, kl, coef) in &self.shift_up[t] {
+                            mk[t] += coef * pow[kl] * mc[l];
+                        }
+                    }
+                }
+            }
+        }
+
+        // Mutual dual tree walk: M2L both ways for well separated pairs, P2P
+        // for close leaves.
+        let mut coef = vec![0.0; len];
+        let mut stack: Vec<(usize, usize)> = vec![(0, 0)];
+        while let Some((ta, tb)) = stack.pop() {
+            let na = &nodes[ta];
+            let nb = &nodes[tb];
+            let d2 = (na.center - nb.center).norm_squared();
+            let r = radius[ta] + radius[tb];
+            if ta == tb {
+                if na.is_leaf() {
+                    p2p_self(p, m, softening, &tree.order[na.start..na.end], a);
+                } else {
+                    for ca in na.children() {
+                        for cb in ca..na.children().end {
+                            stack.push((ca, cb));
+                        }
+                    }
+                }
+            } else if r * r < self.theta * self.theta * d2 && d2.sqrt() - r >= support {
+                let (lo, hi) = (ta.min(tb), ta.max(tb));
+                self.taylor_coefficients(nodes[lo].center - nodes[hi].center, eps2, &mut coef);
+                let (head, tail) = local.split_at_mut(hi * len);
+                let l_lo = &mut head[lo * len..][..len];
+                let l_hi = &mut tail[..len];
+                let m_lo = &multipole[lo * len..][..len];
+                let m_hi = &multipole[hi * len..][..len];
+                for (n, list) in self.m2l.iter().enumerate() {
+                    let mut sum_lo = 0.0;
+                    let mut sum_hi = 0.0;
+                    for &(k, nk, c, c_rev) in list {
+                        sum_lo += c * coef[nk as usize] * m_hi[k as usize];
+                        sum_hi += c_rev * coef[nk as usize] * m_lo[k as usize];
+                    }
+                    l_lo[n] += sum_lo;
+                    l_hi[n] += sum_hi;
+                }
+            } else if na.is_leaf() && nb.is_leaf() {
+                p2p_mutual(
+                    p,
+                    m,
+                    softening,
+                    &tree.order[na.start..na.end],
+                    &tree.order[nb.start..nb.end],
+                    a,
+                );
+            } else if nb.is_leaf() || (!na.is_leaf() && radius[ta] >= radius[tb]) {
+                for ca in na.children() {
+                    stack.push((ca, tb));
+                }
+            } else {
+                for cb in nb.children() {
+                    stack.push((ta, cb));
+                }
+            }
+        }
+
+        // L2L, parents before children, then L2P at the leaves.
+        for k in 0..nodes.len() {
+            let node = &nodes[k];
+            let (head, tail) = local.split_at_mut((k + 1) * len);
+            let lk = &head[k * len..];
+            if node.is_leaf() {
+                for &i in &tree.order[node.start..node.end] {
+                    self.powers(p[i] - node.center, &mut pow);
+                    let mut g = [0.0; 3];
+                    for t in 1..len {
+                        let n = self.terms[t];
+                        for axis in 0..3 {
+                            if n[axis] > 0 {
+                                let mut l = n;
+                                l[axis] -= 1;
+                                g[axis] += n[axis] as f64 * lk[t] * pow[self.lookup(l)];
+                            }
+                        }
+                    }
+                    a[i] += Vec3::new(g[0], g[1], g[2]);
+                }
+            } else {
+                for c in node.children() {
+                    let lc = &mut tail[(c - k - 1) * len..][..len];
+                    self.powers(nodes[c].center - node.center, &mut pow);
+                    for t in 0..len {
+                        for &(n, nm, coef) in &self.shift_down[t] {
+                            lc[t] += coef * pow[nm] * lk[n];
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
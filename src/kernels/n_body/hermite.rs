@@ -0,0 +1,204 @@
+use crate::util;
+use crate::Vec3;
+
+use super::*;
+
+/// Counts of the work done by `HermiteKernel`: the number of block steps, and
+/// the number of body steps, each one a force and jerk evaluation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BlockStats {
+    pub blocks: u64,
+    pub body_steps: u64,
+}
+
+/// 4th order Hermite predictor–corrector (Makino & Aarseth 1992) with
+/// individual block time steps.
+///
+/// Each body has its own step `dt / 2^k`, in whole ticks, so `dt` should be a
+/// power of two for the deepest levels to be available, and the bodies due
+/// at the same tick are advanced together as a block. Everyone is predicted
+/// to the block time from their last acceleration and jerk, the block's
+/// acceleration and jerk are evaluated directly from the predicted positions
+/// and velocities, and the Hermite interpolation of both ends gives the
+/// corrector and the 2nd and 3rd derivatives for the Aarseth criterion
+/// `dt = sqrt(eta (|a| |a2| + |j|^2) / (|j| |a3| + |a2|^2))`. A step may halve
+/// at any time, but only doubles where the doubled step stays in sync with
+/// the blocks. `max_level` bounds the number of halvings.
+#[derive(Clone, Copy, Debug)]
+pub struct HermiteKernel {
+    pub eta: f64,
+    pub eta_start: f64,
+    pub max_level: u32,
+}
+
+impl Default for HermiteKernel {
+    fn default() -> Self {
+        HermiteKernel::new(0.02)
+    }
+}
+
+impl HermiteKernel {
+    pub fn new(eta: f64) -> Self {
+        HermiteKernel {
+            eta: eta,
+            eta_start: eta / 2.0,
+            max_level: 32,
+        }
+    }
+
+    /// Acceleration and jerk of the bodies in `active` from the massive
+    /// bodies, the first `m.len()`.
+    fn calc_a_jerk(
+        p: &[Vec3],
+        v: &[Vec3],
+        m: &[f64],
+        softening: &Softening,
+        active: &[usize],
+        a: &mut [Vec3],
+        jerk: &mut [Vec3],
+    ) {
+        for &i in active {
+            let mut ai = Vec3::ZERO;
+            let mut ji = Vec3::ZERO;
+            for j in 0..m.len() {
+                if j == i {
+                    continue;
+                }
+                let r = p[j] - p[i];
+                let w = v[j] - v[i];
+                let (g, dg) = softening.force_derivative(r.norm_squared());
+                let rw = (r * w).reduce_add() * dg;
+                ai = Vec3::mul_add(r, Vec3::splat(m[j] * g), ai);
+                ji = Vec3::mul_add(
+                    Vec3::mul_add(r, Vec3::splat(rw), w * g),
+                    Vec3::splat(m[j]),
+                    ji,
+                );
+            }
+            a[i] = ai;
+            jerk[i] = ji;
+        }
+    }
+
+    /// The largest level, i.e. the smallest step `dt >> level`, meeting
+    /// `step`, starting the search from `level`.
+    fn level_for(&self, dt: u64, dtf: f64, step: f64, level: u32) -> u32 {
+        let max_level = self.max_level.min(dt.trailing_zeros());
+        let mut level = level;
+        while level < max_level && dtf / (1u64 << level) as f64 > step {
+            level += 1;
+        }
+        return level;
+    }
+
+    pub fn kernel_with_stats(
+        &self,
+        state: PhysicsState,
+        steps: u64,
+        dt: u64,
+    ) -> (PhysicsState, BlockStats) {
+        state.assert_newtonian_only("HermiteKernel", &[]);
+        let n = state.p.len();
+        let dtf = dt as f64 * util::UNIT_TIME;
+        let t_end = steps * dt;
+        let m = scale_m(&state.m[..state.n_massive()], util::GRAVITY_CONSTANT);
+        let all: Vec<usize> = (0..n).collect();
+
+        let mut p = state.p;
+        let mut v = state.v;
+        let mut a = vec![Vec3::ZERO; n];
+        let mut jerk = vec![Vec3::ZERO; n];
+        Self::calc_a_jerk(&p, &v, &m, &state.softening, &all, &mut a, &mut jerk);
+        let mut stats = BlockStats::default();
+
+        // Time of the last step of each body in ticks since the start, and its
+        // step `dt >> level`.
+        let mut t = vec![0u64; n];
+        let mut level = vec![0u32; n];
+        for i in 0..n {
+            let j2 = jerk[i].norm_squared();
+            let step = if j2 > 0.0 {
+                self.eta_start * a[i].norm() / j2.sqrt()
+            } else {
+                dtf
+            };
+            level[i] = self.level_for(dt, dtf, step, 0);
+        }
+
+        let mut pp = p.clone();
+        let mut vp = v.clone();
+        let mut a1 = a.clone();
+        let mut jerk1 = jerk.clone();
+        let mut active = Vec::with_capacity(n);
+        while t.iter().any(|&t| t < t_end) {
+            let t_block = (0..n).map(|i| t[i] + (dt >> level[i])).min().unwrap();
+            active.clear();
+            active.extend((0..n).filter(|&i| t[i] + (dt >> level[i]) == t_block));
+
+            for i in 0..n {
+                let h = (t_block - t[i]) as f64 * util::UNIT_TIME;
+                let h2 = Vec3::splat(h * h / 2.0);
+                let h3 = Vec3::splat(h * h * h / 6.0);
+                pp[i] = Vec3::mul_add(v[i], Vec3::splat(h), p[i]);
+                pp[i] = Vec3::mul_add(a[i], h2, pp[i]);
+                pp[i] = Vec3::mul_add(jerk[i], h3, pp[i]);
+                vp[i] = Vec3::mul_add(a[i], Vec3::splat(h), v[i]);
+                vp[i] = Vec3::mul_add(jerk[i], h2, vp[i]);
+            }
+            Self::calc_a_jerk(&pp, &vp, &m, &state.softening, &active, &mut a1, &mut jerk1);
+
+            for &i in &active {
+                let h = (t_block - t[i]) as f64 * util::UNIT_TIME;
+                let da = a[i] - a1[i];
+                let a2 = (-6.0 * da - h * (4.0 * jerk[i] + 2.0 * jerk1[i])) / (h * h);
+                let a3 = (12.0 * da + 6.0 * h * (jerk[i] + jerk1[i])) / (h * h * h);
+                let h2 = h * h;
+                p[i] = pp[i] + (h2 * h2 / 24.0) * a2 + (h2 * h2 * h / 120.0) * a3;
+                v[i] = vp[i] + (h2 * h / 6.0) * a2 + (h2 * h2 / 24.0) * a3;
+                a[i] = a1[i];
+                jerk[i] = jerk1[i];
+                t[i] = t_block;
+
+                // Aarseth criterion with the derivatives at the new time.
+                let a2 = Vec3::mul_add(a3, Vec3::splat(h), a2);
+                let a_n = a[i].norm();
+                let j_n = jerk[i].norm();
+                let a2_n = a2.norm();
+                let a3_n = a3.norm();
+                let step =
+                    (self.eta * (a_n * a2_n + j_n * j_n) / (j_n * a3_n + a2_n * a2_n)).sqrt();
+
+                let in_sync = level[i] > 0 && t_block % (dt >> (level[i] - 1)) == 0;
+                if step < h {
+                    level[i] = self.level_for(dt, dtf, step, level[i]);
+                } else if step >= 2.0 * h && in_sync {
+                    level[i] -= 1;
+                }
+            }
+            stats.blocks += 1;
+            stats.body_steps += active.len() as u64;
+        }
+
+        let state = PhysicsState {
+            p: p,
+            v: v,
+            m: state.m,
+            r: state.r,
+            n_test: state.n_test,
+            t: state.t + t_end,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        };
+        return (state, stats);
+    }
+}
+
+impl NBodyKernel for HermiteKernel {
+    fn kernel(&self, state: PhysicsState, steps: u64, dt: u64) -> PhysicsState {
+        self.kernel_with_stats(state, steps, dt).0
+    }
+}
@@ -0,0 +1,342 @@
+use crate::util;
+use crate::Vec3;
+
+use super::*;
+
+const MAX_ITERATIONS: usize = 100;
+
+type Spinor = [f64; 4];
+
+#[inline(always)]
+fn dot4(a: &Spinor, b: &Spinor) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
+}
+
+/// Kustaanheimo–Stiefel transform of a relative position and velocity into
+/// `u` and `du/ds`, with the physical time `dt = |r| ds`.
+fn to_ks(r: Vec3, v: Vec3) -> (Spinor, Spinor) {
+    let [x, y, z]: [f64; 3] = r.into();
+    let [vx, vy, vz]: [f64; 3] = v.into();
+    let rn = r.norm();
+    // Of the circle of valid u, pick one away from the u1 = u2 = 0 and
+    // u3 = u4 = 0 singular choices.
+    let u = if x >= 0.0 {
+        let u1 = ((rn + x) / 2.0).sqrt();
+        [u1, y / (2.0 * u1), z / (2.0 * u1), 0.0]
+    } else {
+        let u2 = ((rn - x) / 2.0).sqrt();
+        [y / (2.0 * u2), u2, 0.0, z / (2.0 * u2)]
+    };
+    // du/ds = L(u)^T v / 2
+    let du = [
+        (u[0] * vx + u[1] * vy + u[2] * vz) / 2.0,
+        (-u[1] * vx + u[0] * vy + u[3] * vz) / 2.0,
+        (-u[2] * vx - u[3] * vy + u[0] * vz) / 2.0,
+        (u[3] * vx - u[2] * vy + u[1] * vz) / 2.0,
+    ];
+    return (u, du);
+}
+
+/// Inverse of `to_ks`: `r = L(u) u` and `v = 2 L(u) du/ds / |u|^2`.
+fn from_ks(u: &Spinor, du: &Spinor) -> (Vec3, Vec3) {
+    let r = Vec3::new(
+        u[0] * u[0] - u[1] * u[1] - u[2] * u[2] + u[3] * u[3],
+        2.0 * (u[0] * u[1] - u[2] * u[3]),
+        2.0 * (u[0] * u[2] + u[1] * u[3]),
+    );
+    let v = Vec3::new(
+        u[0] * du[0] - u[1] * du[1] - u[2] * du[2] + u[3] * du[3],
+        u[1] * du[0] + u[0] * du[1] - u[3] * du[2] - u[2] * du[3],
+        u[2] * du[0] + u[3] * du[1] + u[0] * du[2] + u[1] * du[3],
+    ) * (2.0 / dot4(u, u));
+    return (r, v);
+}
+
+/// Advances the relative position `r` and velocity `v` of a pair with
+/// `mu = G (m1 + m2)` by `dt`, exactly.
+///
+/// In KS variables the Kepler problem is the harmonic oscillator
+/// `u'' = h u / 2`, `h` being the specific energy, which stays regular through
+/// a collision. Its solution `u = u0 c0(l s^2) + u0' s c1(l s^2)` with
+/// `l = -h / 2` holds for every conic, and the physical time
+/// `t(s) = integral |u|^2 ds` has a closed form in the same Stumpff
+/// functions, so the only iteration is solving `t(s) = dt`.
+pub fn ks_drift(r: &mut Vec3, v: &mut Vec3, mu: f64, dt: f64) {
+    let (u0, du0) = to_ks(*r, *v);
+    let a = dot4(&u0, &u0);
+    let b = dot4(&u0, &du0);
+    let c = dot4(&du0, &du0);
+    let h = (2.0 * c - mu) / a;
+    let l = -h / 2.0;
+    let dt = if h < 0.0 {
+        dt % (2.0 * std::f64::consts::PI * mu / (-2.0 * h).powf(1.5))
+    } else {
+        dt
+    };
+    if dt == 0.0 {
+        return;
+    }
+
+    let state = |s: f64| {
+        let k = stumpff(l * s * s);
+        let mut u = [0.0; 4];
+        let mut du = [0.0; 4];
+        for n in 0..4 {
+            u[n] = u0[n] * k[0] + du0[n] * s * k[1];
+            du[n] = -l * s * k[1] * u0[n] + du0[n] * k[0];
+        }
+        (u, du)
+    };
+    // t(s) with the double angle forms of c0^2, s c0 c1 and s^2 c1^2. It
+    // increases with s, and overflows only on hyperbolic orbits far past any
+    // reasonable dt.
+    let time = |s: f64| {
+        let k = stumpff(4.0 * l * s * s);
+        let t = a * s * (1.0 + k[1]) / 2.0 + 2.0 * b * s * s * k[2] + 2.0 * c * s * s * s * k[3];
+        if t.is_finite() {
+            t
+        } else {
+            f64::INFINITY
+        }
+    };
+
+    // Bracket the root from the first order guess, then refine with Newton's
+    // method (dt/ds = |u|^2), bisecting when a step leaves the bracket or
+    // converges slower than bisection would.
+    let mut s = dt / a;
+    let mut lo = 0.0;
+    while time(s) < dt {
+        lo = s;
+        s *= 2.0;
+    }
+    let mut hi = s;
+    let mut ds_old = f64::INFINITY;
+    for _ in 0..MAX_ITERATIONS {
+        let f = time(s) - dt;
+        if f < 0.0 {
+            lo = s;
+        } else {
+            hi = s;
+        }
+        let (u, _) = state(s);
+        let mut s_new = s - f / dot4(&u, &u);
+        if !(s_new > lo && s_new < hi) || (s_new - s).abs() > 0.5 * ds_old.abs() {
+            s_new = (lo + hi) / 2.0;
+        }
+        let ds = s_new - s;
+        ds_old = ds;
+        s = s_new;
+        if ds.abs() <= 1e-15 * s {
+            break;
+        }
+    }
+
+    let (u, du) = state(s);
+    (*r, *v) = from_ks(&u, &du);
+}
+
+/// A pair entering or leaving regularization, with the total energy error
+/// accumulated since the start of the run at that moment.
+#[derive(Clone, Copy, Debug)]
+pub struct KsTransition {
+    pub t: u64,
+    pub pair: (usize, usize),
+    pub regularized: bool,
+    pub energy_error: f64,
+}
+
+/// Leapfrog with Kustaanheimo–Stiefel regularized close pairs.
+///
+/// The Hamiltonian is split into the Kepler problems of the regularized pairs
+/// plus the free motion of everyone else, and the remaining interactions.
+/// Pairs are drifted with `ks_drift`, so their own mutual force, which is what
+/// blows up in `calc_a` as the separation goes to zero, never enters a kick.
+/// A pair is regularized when it comes within `r_on` and resolved again once
+/// it separates beyond `r_off`; the gap keeps pairs from flickering in and out
+/// around a single threshold. Each body is in at most one pair, and a closer
+/// encounter takes over from a wider pair. A softened pair is not a Kepler
+/// problem, so softened states are rejected.
+#[derive(Clone, Copy, Debug)]
+pub struct KsRegularizedKernel {
+    pub r_on: f64,
+    pub r_off: f64,
+}
+
+impl KsRegularizedKernel {
+    pub fn new(r_on: f64) -> Self {
+        KsRegularizedKernel {
+            r_on: r_on,
+            r_off: 2.0 * r_on,
+        }
+    }
+
+    /// Accelerations from every interaction except the regularized pairs and
+    /// those between the test particles after the first `n_massive` bodies,
+    /// and from the external potentials.
+    fn calc_a(
+        p: &[Vec3],
+        m: &[f64],
+        n_massive: usize,
+        softening: &Softening,
+        external: &[Arc<dyn ExternalPotential>],
+        partner: &[Option<usize>],
+        a: &mut [Vec3],
+    ) {
+        a.fill(Vec3::ZERO);
+        for i in 0..n_massive {
+            let mi = Vec3::splat(m[i]);
+            for j in (i + 1)..p.len() {
+                if partner[i] == Some(j) {
+                    continue;
+                }
+                let r = softening.calc_r(&p[i], &p[j]);
+                a[i] = Vec3::mul_add(r, Vec3::splat(m[j]), a[i]);
+                a[j] = Vec3::mul_neg_add(r, mi, a[j]);
+            }
+        }
+        add_external(p, 1.0, external, 1.0, a);
+    }
+
+    /// Releases pairs beyond `r_off` and forms new pairs within `r_on`,
+    /// closest first, breaking up any wider pair a member was in.
+    fn update_pairs(
+        &self,
+        state: &PhysicsState,
+        partner: &mut [Option<usize>],
+        e0: f64,
+        log: &mut Vec<KsTransition>,
+    ) {
+        let n = state.p.len();
+        let mut changed = vec![];
+        for i in 0..n {
+            if let Some(j) = partner[i] {
+                if i < j && (state.p[i] - state.p[j]).norm() > self.r_off {
+                    partner[i] = None;
+                    partner[j] = None;
+                    changed.push(((i, j), false));
+                }
+            }
+        }
+        let mut close = vec![];
+        // Test particles only pair with massive bodies.
+        for i in 0..state.n_massive() {
+            for j in (i + 1)..n {
+                let r = (state.p[i] - state.p[j]).norm();
+                if r < self.r_on && state.m[i] + state.m[j] > 0.0 {
+                    close.push((r, i, j));
+                }
+            }
+        }
+        close.sort_by(|x, y| x.0.total_cmp(&y.0));
+        for (r, i, j) in close {
+            if partner[i] == Some(j) {
+                continue;
+            }
+            let wider = |k: usize| match partner[k] {
+                None => true,
+                Some(l) => r < (state.p[k] - state.p[l]).norm(),
+            };
+            if wider(i) && wider(j) {
+                for k in [i, j] {
+                    if let Some(l) = partner[k] {
+                        partner[k] = None;
+                        partner[l] = None;
+                        changed.push(((k.min(l), k.max(l)), false));
+                    }
+                }
+                partner[i] = Some(j);
+                partner[j] = Some(i);
+                changed.push(((i, j), true));
+            }
+        }
+        if !changed.is_empty() {
+            let energy_error = state.calc_total_energy() - e0;
+            for (pair, regularized) in changed {
+                log.push(KsTransition {
+                    t: state.t,
+                    pair: pair,
+                    regularized: regularized,
+                    energy_error: energy_error,
+                });
+            }
+        }
+    }
+
+    /// Like `kernel`, but also returns every pair transition. Pairs are
+    /// not carried over between calls, so regularized pairs show up again at
+    /// the start of the next one.
+    pub fn kernel_logged(
+        &self,
+        state: PhysicsState,
+        steps: u64,
+        dt: u64,
+    ) -> (PhysicsState, Vec<KsTransition>) {
+        assert!(
+            state.softening == Softening::None,
+            "KsRegularizedKernel can't regularize softened pairs"
+        );
+        state.assert_newtonian_only("KsRegularizedKernel", &[Extension::External]);
+        let n = state.p.len();
+        let dtf = dt as f64 * util::UNIT_TIME;
+        let half = Vec3::splat(dtf / 2.0);
+        let m = scale_m(&state.m, util::GRAVITY_CONSTANT);
+        let e0 = state.calc_total_energy();
+
+        let mut log = vec![];
+        let mut partner = vec![None; n];
+        let mut state = state;
+        let mut a = vec![Vec3::ZERO; n];
+        for _ in 0..steps {
+            self.update_pairs(&state, &mut partner, e0, &mut log);
+
+            Self::calc_a(
+                &state.p,
+                &m,
+                state.n_massive(),
+                &state.softening,
+                &state.external,
+                &partner,
+                &mut a,
+            );
+            advance(&mut state.v, &a, &half);
+            for i in 0..n {
+                match partner[i] {
+                    None => state.p[i] = Vec3::mul_add(state.v[i], Vec3::splat(dtf), state.p[i]),
+                    Some(j) if i < j => {
+                        let (mi, mj) = (state.m[i], state.m[j]);
+                        let mt = mi + mj;
+                        let com = (mi * state.p[i] + mj * state.p[j]) / mt;
+                        let v_com = (mi * state.v[i] + mj * state.v[j]) / mt;
+                        let mut r = state.p[j] - state.p[i];
+                        let mut v = state.v[j] - state.v[i];
+                        ks_drift(&mut r, &mut v, m[i] + m[j], dtf);
+                        let com = Vec3::mul_add(v_com, Vec3::splat(dtf), com);
+                        state.p[i] = com - r * (mj / mt);
+                        state.p[j] = com + r * (mi / mt);
+                        state.v[i] = v_com - v * (mj / mt);
+                        state.v[j] = v_com + v * (mi / mt);
+                    }
+                    Some(_) => {}
+                }
+            }
+            Self::calc_a(
+                &state.p,
+                &m,
+                state.n_massive(),
+                &state.softening,
+                &state.external,
+                &partner,
+                &mut a,
+            );
+            advance(&mut state.v, &a, &half);
+            state.t += dt;
+        }
+        return (state, log);
+    }
+}
+
+impl NBodyKernel for KsRegularizedKernel {
+    fn kernel(&self, state: PhysicsState, steps: u64, dt: u64) -> PhysicsState {
+        self.kernel_logged(state, steps, dt).0
+    }
+}
@@ -0,0 +1,155 @@
+use crate::Vec3;
+
+const MAX_DEPTH: u32 = 48;
+
+/// An octree over a set of positions, shared by the tree-based force solvers.
+///
+/// Bodies are never moved: `order` is a permutation of body indices such that
+/// every node owns the contiguous range `order[start..end]`. The nonempty
+/// children of a node are stored contiguously starting at `first_child`, and
+/// every child comes after its parent in `nodes`, so iterating `nodes` in
+/// reverse visits children before parents.
+pub struct Octree {
+    pub nodes: Vec<OctreeNode>,
+    pub order: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct OctreeNode {
+    pub center: Vec3,
+    pub half_width: f64,
+    pub start: usize,
+    pub end: usize,
+    pub first_child: usize,
+    pub child_count: usize,
+}
+
+impl OctreeNode {
+    #[inline]
+    pub fn is_leaf(&self) -> bool {
+        self.child_count == 0
+    }
+
+    #[inline]
+    pub fn children(&self) -> core::ops::Range<usize> {
+        self.first_child..(self.first_child + self.child_count)
+    }
+}
+
+impl Octree {
+    /// Builds the tree, splitting nodes until they hold at most `leaf_size`
+    /// bodies.
+    pub fn new(p: &[Vec3], leaf_size: usize) -> Self {
+        let mut lo = [f64::INFINITY; 3];
+        let mut hi = [f64::NEG_INFINITY; 3];
+        for pi in p {
+            let x: [f64; 3] = (*pi).into();
+            for k in 0..3 {
+                lo[k] = lo[k].min(x[k]);
+                hi[k] = hi[k].max(x[k]);
+            }
+        }
+        let mut half_width: f64 = 0.0;
+        for k in 0..3 {
+            half_width = half_width.max((hi[k] - lo[k]) / 2.0);
+        }
+        // Keep bodies on the boundary strictly inside the root box.
+        half_width = half_width * (1.0 + 1e-9) + f64::MIN_POSITIVE;
+        let center = if p.is_empty() {
+            Vec3::ZERO
+        } else {
+            Vec3::new(
+                (lo[0] + hi[0]) / 2.0,
+                (lo[1] + hi[1]) / 2.0,
+                (lo[2] + hi[2]) / 2.0,
+            )
+        };
+
+        let mut tree = Octree {
+            nodes: vec![OctreeNode {
+                center,
+                half_width,
+                start: 0,
+                end: p.len(),
+                first_child: 0,
+                child_count: 0,
+            }],
+            order: (0..p.len()).collect(),
+        };
+        let mut scratch = vec![0; p.len()];
+        tree.split(0, p, leaf_size.max(1), 0, &mut scratch);
+        return tree;
+    }
+
+    fn split(
+        &mut self,
+        node: usize,
+        p: &[Vec3],
+        leaf_size: usize,
+        depth: u32,
+        scratch: &mut [usize],
+    ) {
+        let OctreeNode {
+            center,
+            half_width,
+            start,
+            end,
+            ..
+        } = self.nodes[node];
+        if end - start <= leaf_size || depth >= MAX_DEPTH {
+            return;
+        }
+
+        // Counting sort of the node's bodies by octant.
+        let c: [f64; 3] = center.into();
+        let octant = |i: usize| {
+            let x: [f64; 3] = p[i].into();
+            (x[0] >= c[0]) as usize
+                | ((x[1] >= c[1]) as usize) << 1
+                | ((x[2] >= c[2]) as usize) << 2
+        };
+        let mut count = [0usize; 8];
+        for &i in &self.order[start..end] {
+            count[octant(i)] += 1;
+        }
+        let mut offset = [0usize; 8];
+        for k in 1..8 {
+            offset[k] = offset[k - 1] + count[k - 1];
+        }
+        let mut fill = offset;
+        for &i in &self.order[start..end] {
+            let o = octant(i);
+            scratch[fill[o]] = i;
+            fill[o] += 1;
+        }
+        self.order[start..end].copy_from_slice(&scratch[..(end - start)]);
+
+        let first_child = self.nodes.len();
+        let quarter = half_width / 2.0;
+        for k in 0..8 {
+            if count[k] == 0 {
+                continue;
+            }
+            let shift = Vec3::new(
+                if k & 1 != 0 { quarter } else { -quarter },
+                if k & 2 != 0 { quarter } else { -quarter },
+                if k & 4 != 0 { quarter } else { -quarter },
+            );
+            self.nodes.push(OctreeNode {
+                center: center + shift,
+                half_width: quarter,
+                start: start + offset[k],
+                end: start + offset[k] + count[k],
+                first_child: 0,
+                child_count: 0,
+            });
+        }
+        let child_count = self.nodes.len() - first_child;
+        self.nodes[node].first_child = first_child;
+        self.nodes[node].child_count = child_count;
+
+        for child in first_child..(first_child + child_count) {
+            self.split(child, p, leaf_size, depth + 1, scratch);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::marker::PhantomData;
+
+use crate::{util, Scalar, Vec3, Vector};
+
+use super::*;
+
+/// Classic 4th order Runge–Kutta at the precision of `V`, like
+/// `VelVerletKernel`, and the one N-body kernel taking the post-Newtonian
+/// corrections.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RK4Kernel<F: ForceSolver<V> = DirectSummation, V: Vector = Vec3> {
+    pub solver: F,
+    precision: PhantomData<V>,
+}
+
+impl<F: ForceSolver<V>, V: Vector> RK4Kernel<F, V> {
+    pub fn new(solver: F) -> Self {
+        RK4Kernel {
+            solver,
+            precision: PhantomData,
+        }
+    }
+}
+
+impl<F: ForceSolver<V>, V: Vector> NBodyKernel for RK4Kernel<F, V> {
+    fn kernel(&self, state: PhysicsState, steps: u64, dt: u64) -> PhysicsState {
+        let n = state.p.len();
+        let (length, time) = units(&state);
+        let dtf = V::Scalar::from_f64(dt as f64 * util::UNIT_TIME / time);
+        let a_scale = time * time / length;
+
+        let mu = scale_m(&state.m, util::GRAVITY_CONSTANT);
+        let m: Vec<V::Scalar> = scale_m(&mu, time * time / (length * length * length))
+            .iter()
+            .map(|&m| V::Scalar::from_f64(m))
+            .collect();
+        let n_massive = state.n_massive();
+        let softening = state.softening.in_units(length);
+        let post_newtonian = state.post_newtonian;
+        let force_law = &state.force_law;
+        let external = &state.external;
+        let calc_a = |p: &[V], v: &[V], a: &mut [V]| {
+            match force_law {
+                None => calc_a_massive(&self.solver, p, &m[..n_massive], &softening, a),
+                Some(force_law) => calc_a_force_law_in_units(
+                    p,
+                    length,
+                    &mu,
+                    n_massive,
+                    force_law.as_ref(),
+                    a_scale,
+                    a,
+                ),
+            }
+            add_external(p, length, external, a_scale, a);
+            if post_newtonian.is_enabled() {
+                add_post_newtonian(p, v, (length, time), &post_newtonian, &mu, a_scale, a);
+            }
+        };
+
+        let c = V::Scalar::from_f64;
+        let dtm = V::splat(dtf);
+        let dtm2 = V::splat(dtf / c(2.0));
+        let dtm3 = V::splat(dtf / c(3.0));
+        let dtm6 = V::splat(dtf / c(6.0));
+
+        let mut p = to_units::<V>(&state.p, length);
+        let mut v = to_units::<V>(&state.v, length / time);
+
+        let mut ps = vec![V::ZERO; n];
+        let mut k1v = vec![V::ZERO; n];
+        let mut k2v = vec![V::ZERO; n];
+        let mut k3v = vec![V::ZERO; n];
+        let mut k4v = vec![V::ZERO; n];
+        let mut k2r = vec![V::ZERO; n];
+        let mut k3r = vec![V::ZERO; n];
+        let mut k4r = vec![V::ZERO; n];
+
+        for _ in 0..steps {
+            calc_a(&p, &v, &mut k1v);
+
+            ps.copy_from_slice(&p);
+            advance(&mut ps, &v, &dtm2);
+            k2r.copy_from_slice(&v);
+            advance(&mut k2r, &k1v, &dtm2);
+            calc_a(&ps, &k2r, &mut k2v);
+
+            ps.copy_from_slice(&p);
+            advance(&mut ps, &k2r, &dtm2);
+            k3r.copy_from_slice(&v);
+            advance(&mut k3r, &k2v, &dtm2);
+            calc_a(&ps, &k3r, &mut k3v);
+
+            ps.copy_from_slice(&p);
+            advance(&mut ps, &k3r, &dtm);
+            k4r.copy_from_slice(&v);
+            advance(&mut k4r, &k3v, &dtm);
+            calc_a(&ps, &k4r, &mut k4v);
+
+            advance(&mut p, &v, &dtm6);
+            advance(&mut p, &k2r, &dtm3);
+            advance(&mut p, &k3r, &dtm3);
+            advance(&mut p, &k4r, &dtm6);
+
+            advance(&mut v, &k1v, &dtm6);
+            advance(&mut v, &k2v, &dtm3);
+            advance(&mut v, &k3v, &dtm3);
+            advance(&mut v, &k4v, &dtm6);
+        }
+
+        PhysicsState {
+            p: from_units(&p, length),
+            v: from_units(&v, length / time),
+            m: state.m,
+            r: state.r,
+            n_test: state.n_test,
+            t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        }
+    }
+}
@@ -0,0 +1,291 @@
+#[cfg(target_feature = "avx2")]
+use core::arch::x86_64::*;
+use std::ops::Range;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::Vec3;
+
+use super::*;
+
+/// Direct summation over a structure-of-arrays copy of the bodies.
+///
+/// Instead of vectorizing within a `Vec3`, each `__m256d` holds one coordinate
+/// of four source bodies, so no lane is wasted on padding. Targets are split
+/// into contiguous chunks, one per thread, handled by worker threads started
+/// by `new` and shared by the clones of the solver, so an evaluation costs a
+/// message per thread instead of spawning them. Every ordered pair is
+/// evaluated, trading the factor of two saved by symmetry in `calc_a` for
+/// independent per-target accumulators. Plummer softening costs one more add;
+/// spline softening corrects the few lanes within its support after the fact.
+/// Only the target's own lane is skipped, so coincident bodies give NaN
+/// without softening, as in `calc_a`.
+#[derive(Clone, Debug)]
+pub struct SimdDirectSummation {
+    // Only for more than one thread, so only set through `new`.
+    pool: Option<Arc<Pool>>,
+}
+
+impl SimdDirectSummation {
+    pub fn new(threads: usize) -> Self {
+        SimdDirectSummation {
+            pool: if threads > 1 {
+                Some(Arc::new(Pool::new(threads)))
+            } else {
+                None
+            },
+        }
+    }
+}
+
+impl Default for SimdDirectSummation {
+    fn default() -> Self {
+        SimdDirectSummation::new(std::thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+/// Source bodies, padded with massless bodies to a multiple of four.
+struct Soa {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    z: Vec<f64>,
+    m: Vec<f64>,
+    // Number of bodies before the padding.
+    n: usize,
+}
+
+impl Soa {
+    fn new(p: &[Vec3], m: &[f64]) -> Self {
+        let len = p.len().div_ceil(4) * 4;
+        let mut soa = Soa {
+            x: vec![0.0; len],
+            y: vec![0.0; len],
+            z: vec![0.0; len],
+            m: vec![0.0; len],
+            n: p.len(),
+        };
+        for i in 0..p.len() {
+            let pi: [f64; 3] = p[i].into();
+            soa.x[i] = pi[0];
+            soa.y[i] = pi[1];
+            soa.z[i] = pi[2];
+            soa.m[i] = m[i];
+        }
+        return soa;
+    }
+
+    /// Acceleration at `target` from every source except `skip`, the index of
+    /// the target among the sources if it is one of them.
+    #[cfg(target_feature = "avx2")]
+    #[inline]
+    fn accel(&self, target: Vec3, skip: Option<usize>, softening: &Softening) -> Vec3 {
+        let t: [f64; 3] = target.into();
+        let (eps2, h2) = match *softening {
+            Softening::None => (0.0, 0.0),
+            Softening::Plummer(eps) => (eps * eps, 0.0),
+            Softening::Spline(_) => (0.0, softening.support() * softening.support()),
+        };
+        unsafe {
+            let tx = _mm256_set1_pd(t[0]);
+            let ty = _mm256_set1_pd(t[1]);
+            let tz = _mm256_set1_pd(t[2]);
+            let eps2v = _mm256_set1_pd(eps2);
+            let h2v = _mm256_set1_pd(h2);
+            let zero = _mm256_setzero_pd();
+            let mut ax = zero;
+            let mut ay = zero;
+            let mut az = zero;
+            for j in (0..self.m.len()).step_by(4) {
+                let dx = _mm256_sub_pd(_mm256_loadu_pd(self.x.as_ptr().add(j)), tx);
+                let dy = _mm256_sub_pd(_mm256_loadu_pd(self.y.as_ptr().add(j)), ty);
+                let dz = _mm256_sub_pd(_mm256_loadu_pd(self.z.as_ptr().add(j)), tz);
+                let r2 = _mm256_mul_pd(dx, dx);
+                let r2 = _mm256_fmadd_pd(dy, dy, r2);
+                let r2 = _mm256_fmadd_pd(dz, dz, r2);
+                let s2 = _mm256_add_pd(r2, eps2v);
+                let r3 = _mm256_mul_pd(s2, _mm256_sqrt_pd(s2));
+                let mut s = _mm256_div_pd(_mm256_loadu_pd(self.m.as_ptr().add(j)), r3);
+                let close = _mm256_movemask_pd(_mm256_cmp_pd(r2, h2v, _CMP_LT_OQ));
+                if close != 0 {
+                    let mut r2s = [0.0; 4];
+                    let mut ss = [0.0; 4];
+                    _mm256_storeu_pd(r2s.as_mut_ptr(), r2);
+                    _mm256_storeu_pd(ss.as_mut_ptr(), s);
+                    for l in 0..4 {
+                        if close & (1 << l) != 0 {
+                            ss[l] = self.m[j + l] * softening.force(r2s[l]);
+                        }
+                    }
+                    s = _mm256_loadu_pd(ss.as_ptr());
+                }
+                // The target itself gives 0/0, or a finite self force with
+                // softening, and so does the padding for a target at the
+                // origin.
+                if j + 4 > self.n || skip.is_some_and(|i| (j..j + 4).contains(&i)) {
+                    let mut ss = [0.0; 4];
+                    _mm256_storeu_pd(ss.as_mut_ptr(), s);
+                    for l in 0..4 {
+                        if j + l >= self.n || skip == Some(j + l) {
+                            ss[l] = 0.0;
+                        }
+                    }
+                    s = _mm256_loadu_pd(ss.as_ptr());
+                }
+                ax = _mm256_fmadd_pd(dx, s, ax);
+                ay = _mm256_fmadd_pd(dy, s, ay);
+                az = _mm256_fmadd_pd(dz, s, az);
+            }
+            let mut sx = [0.0; 4];
+            let mut sy = [0.0; 4];
+            let mut sz = [0.0; 4];
+            _mm256_storeu_pd(sx.as_mut_ptr(), ax);
+            _mm256_storeu_pd(sy.as_mut_ptr(), ay);
+            _mm256_storeu_pd(sz.as_mut_ptr(), az);
+            Vec3::new(
+                (sx[0] + sx[1]) + (sx[2] + sx[3]),
+                (sy[0] + sy[1]) + (sy[2] + sy[3]),
+                (sz[0] + sz[1]) + (sz[2] + sz[3]),
+            )
+        }
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    #[inline]
+    fn accel(&self, target: Vec3, skip: Option<usize>, softening: &Softening) -> Vec3 {
+        let t: [f64; 3] = target.into();
+        let mut a = [0.0; 3];
+        for j in 0..self.n {
+            if skip == Some(j) {
+                continue;
+            }
+            let d = [self.x[j] - t[0], self.y[j] - t[1], self.z[j] - t[2]];
+            let r2 = d[0] * d[0] + d[1] * d[1] + d[2] * d[2];
+            let s = self.m[j] * softening.force(r2);
+            for k in 0..3 {
+                a[k] += d[k] * s;
+            }
+        }
+        Vec3::new(a[0], a[1], a[2])
+    }
+}
+
+/// A chunk of targets for a worker of `Pool`.
+struct Job {
+    soa: Arc<Soa>,
+    targets: Arc<Vec<Vec3>>,
+    // Whether the targets are the sources, each to skip itself.
+    sources: bool,
+    range: Range<usize>,
+    softening: Softening,
+}
+
+/// Worker threads waiting for jobs, which end when the pool is dropped.
+#[derive(Debug)]
+struct Pool {
+    jobs: Vec<Sender<Job>>,
+    // Locked for a whole evaluation, so concurrent ones don't mix results.
+    results: Mutex<Receiver<(usize, Vec<Vec3>)>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Pool {
+    fn new(threads: usize) -> Self {
+        let (results_tx, results) = channel();
+        let mut jobs = vec![];
+        let mut workers = vec![];
+        for _ in 0..threads {
+            let (jobs_tx, jobs_rx) = channel::<Job>();
+            let results_tx = results_tx.clone();
+            workers.push(std::thread::spawn(move || {
+                for job in jobs_rx {
+                    let a = job
+                        .range
+                        .clone()
+                        .map(|i| {
+                            let skip = if job.sources { Some(i) } else { None };
+                            job.soa.accel(job.targets[i], skip, &job.softening)
+                        })
+                        .collect();
+                    if results_tx.send((job.range.start, a)).is_err() {
+                        return;
+                    }
+                }
+            }));
+            jobs.push(jobs_tx);
+        }
+        return Pool {
+            jobs: jobs,
+            results: Mutex::new(results),
+            workers: workers,
+        };
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        // Closing the job channels ends the workers.
+        self.jobs.clear();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl SimdDirectSummation {
+    /// Accelerations of the bodies at `targets` from the sources in `soa`,
+    /// which are the targets themselves if `sources`.
+    fn calc_a_soa(
+        &self,
+        soa: Soa,
+        targets: &[Vec3],
+        sources: bool,
+        softening: &Softening,
+        a: &mut [Vec3],
+    ) {
+        let Some(pool) = &self.pool else {
+            for i in 0..targets.len() {
+                let skip = if sources { Some(i) } else { None };
+                a[i] = soa.accel(targets[i], skip, softening);
+            }
+            return;
+        };
+        let results = pool.results.lock().unwrap();
+        let soa = Arc::new(soa);
+        let targets = Arc::new(targets.to_vec());
+        let chunk = targets.len().div_ceil(pool.jobs.len()).max(1);
+        let mut count = 0;
+        for (k, start) in (0..targets.len()).step_by(chunk).enumerate() {
+            let job = Job {
+                soa: soa.clone(),
+                targets: targets.clone(),
+                sources: sources,
+                range: start..(start + chunk).min(targets.len()),
+                softening: *softening,
+            };
+            pool.jobs[k].send(job).unwrap();
+            count += 1;
+        }
+        for _ in 0..count {
+            let (start, a_chunk) = results.recv().unwrap();
+            a[start..start + a_chunk.len()].copy_from_slice(&a_chunk);
+        }
+    }
+}
+
+impl ForceSolver for SimdDirectSummation {
+    fn calc_a(&self, p: &[Vec3], m: &[f64], softening: &Softening, a: &mut [Vec3]) {
+        self.calc_a_soa(Soa::new(p, m), p, true, softening, a);
+    }
+
+    fn calc_a_test(
+        &self,
+        p: &[Vec3],
+        m: &[f64],
+        softening: &Softening,
+        p_test: &[Vec3],
+        a_test: &mut [Vec3],
+    ) {
+        self.calc_a_soa(Soa::new(p, m), p_test, false, softening, a_test);
+    }
+}
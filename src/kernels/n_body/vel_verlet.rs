@@ -0,0 +1,98 @@
+use std::marker::PhantomData;
+
+use crate::util;
+use crate::{Scalar, Vec3, Vector};
+
+use super::*;
+
+/// Velocity Verlet at the precision of `V`, `Vec3` by default and `Vec3f`
+/// for quick runs and large ensembles in single precision, with direct
+/// summation, the one solver generic over precision. Like the three-body
+/// kernel, it works in the units of `natural_scales`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VelVerletKernel<F: ForceSolver<V> = DirectSummation, V: Vector = Vec3> {
+    pub solver: F,
+    precision: PhantomData<V>,
+}
+
+impl<F: ForceSolver<V>, V: Vector> VelVerletKernel<F, V> {
+    pub fn new(solver: F) -> Self {
+        VelVerletKernel {
+            solver,
+            precision: PhantomData,
+        }
+    }
+}
+
+impl<F: ForceSolver<V>, V: Vector> NBodyKernel for VelVerletKernel<F, V> {
+    fn kernel(&self, state: PhysicsState, steps: u64, dt: u64) -> PhysicsState {
+        state.assert_newtonian_only(
+            "VelVerletKernel",
+            &[Extension::External, Extension::ForceLaw],
+        );
+        let n = state.p.len();
+
+        let (length, time) = units(&state);
+        let dtf = dt as f64 * util::UNIT_TIME / time;
+        let modified_g =
+            util::GRAVITY_CONSTANT * dtf * dtf * (time * time / (length * length * length));
+        let a_scale = dtf * dtf * (time * time / length);
+
+        let m: Vec<V::Scalar> = scale_m(&state.m, modified_g)
+            .iter()
+            .map(|&m| V::Scalar::from_f64(m))
+            .collect();
+        let mu = scale_m(&state.m, util::GRAVITY_CONSTANT);
+        let n_massive = state.n_massive();
+        let softening = state.softening.in_units(length);
+        let force_law = &state.force_law;
+        let external = &state.external;
+        let calc_a = |p: &[V], a: &mut [V]| {
+            match force_law {
+                None => calc_a_massive(&self.solver, p, &m[..n_massive], &softening, a),
+                Some(force_law) => calc_a_force_law_in_units(
+                    p,
+                    length,
+                    &mu,
+                    n_massive,
+                    force_law.as_ref(),
+                    a_scale,
+                    a,
+                ),
+            }
+            add_external(p, length, external, a_scale, a);
+        };
+
+        let mut p = to_units::<V>(&state.p, length);
+        let mut v = to_units::<V>(&state.v, length / time);
+        scale(&mut v, V::Scalar::from_f64(dtf));
+
+        let half = V::splat(V::Scalar::from_f64(0.5));
+        let mut a = vec![V::ZERO; n];
+        let mut a2 = vec![V::ZERO; n];
+        calc_a(&p, &mut a);
+        for _ in 0..steps {
+            add(&mut p, &v);
+            advance(&mut p, &a, &half);
+            calc_a(&p, &mut a2);
+            advance(&mut v, &a, &half);
+            advance(&mut v, &a2, &half);
+            std::mem::swap(&mut a, &mut a2);
+        }
+        scale(&mut v, V::Scalar::from_f64(1.0 / dtf));
+        PhysicsState {
+            p: from_units(&p, length),
+            v: from_units(&v, length / time),
+            m: state.m,
+            r: state.r,
+            n_test: state.n_test,
+            t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use crate::util;
+use crate::Vec3;
+
+use super::*;
+
+const MAX_ITERATIONS: usize = 100;
+
+/// Wisdom–Holman symplectic mapping in democratic heliocentric coordinates
+/// (Duncan, Levison & Lee 1998), for systems dominated by body 0.
+///
+/// Each body orbits body 0 on an exact Kepler drift in heliocentric position
+/// and barycentric velocity, the mutual attraction of the other bodies is
+/// applied as a kick computed by `solver`, and the motion of body 0 enters as
+/// a linear drift of all heliocentric positions. The error is proportional to
+/// the ratio of the other masses to the central one, so steps can be a
+/// sizeable fraction of the shortest orbital period. The drifts stay
+/// Keplerian under softening, and the difference between the softened and the
+/// Newtonian attraction of body 0 is added to the kicks. Body 0 must have
+/// mass.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WisdomHolmanKernel<F: ForceSolver = DirectSummation> {
+    pub solver: F,
+}
+
+impl<F: ForceSolver> WisdomHolmanKernel<F> {
+    pub fn new(solver: F) -> Self {
+        WisdomHolmanKernel { solver }
+    }
+}
+
+#[inline(always)]
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    (a * b).reduce_add()
+}
+
+/// Advances `p`, `v` along the Kepler orbit around a mass with `mu = G M` at
+/// the origin, using universal variables so that every conic is handled the
+/// same way.
+pub fn kepler_drift(p: &mut Vec3, v: &mut Vec3, mu: f64, dt: f64) {
+    let r0 = p.norm();
+    let eta0 = dot(*p, *v);
+    let beta = 2.0 * mu / r0 - v.norm_squared();
+    // Bound orbits are periodic, which keeps x small for long drifts.
+    let dt = if beta > 0.0 {
+        dt % (2.0 * std::f64::consts::PI * mu / beta.powf(1.5))
+    } else {
+        dt
+    };
+    if dt == 0.0 {
+        return;
+    }
+
+    // G_k(x) = x^k c_k(beta x^2).
+    let g = |x: f64| {
+        let c = stumpff(beta * x * x);
+        [c[0], x * c[1], x * x * c[2], x * x * x * c[3]]
+    };
+    // Kepler's equation r0 G1 + eta0 G2 + mu G3 = dt. Its left side increases
+    // with x, and overflows only far out on a hyperbola, where it is huge and
+    // has the sign of x.
+    let kepler = |gs: &[f64; 4], x: f64| {
+        let f = r0 * gs[1] + eta0 * gs[2] + mu * gs[3] - dt;
+        if f.is_finite() {
+            f
+        } else {
+            x.signum() * f64::INFINITY
+        }
+    };
+
+    // Bracket the root starting from the first order guess, then refine with
+    // Laguerre–Conway, falling back to bisection whenever a step leaves the
+    // bracket or converges slower than bisection would, as it does on the
+    // exponential tail of a hyperbola.
+    let mut x = dt / r0;
+    let (mut lo, mut hi) = (0.0f64.min(x), 0.0f64.max(x));
+    while kepler(&g(x), x) * dt.signum() < 0.0 {
+        x *= 2.0;
+        (lo, hi) = (0.0f64.min(x), 0.0f64.max(x));
+    }
+    let n = 5.0;
+    let mut gs = g(x);
+    let mut dx_old = f64::INFINITY;
+    for _ in 0..MAX_ITERATIONS {
+        let f = kepler(&gs, x);
+        if f < 0.0 {
+            lo = x;
+        } else {
+            hi = x;
+        }
+        let df = r0 * gs[0] + eta0 * gs[1] + mu * gs[2];
+        let ddf = eta0 * gs[0] + (mu - beta * r0) * gs[1];
+        let disc = ((n - 1.0) * (n - 1.0) * df * df - n * (n - 1.0) * f * ddf)
+            .abs()
+            .sqrt();
+        let mut x_new = x - n * f / (df + df.signum() * disc);
+        if !(x_new > lo && x_new < hi) || (x_new - x).abs() > 0.5 * dx_old.abs() {
+            x_new = (lo + hi) / 2.0;
+        }
+        let dx = x_new - x;
+        dx_old = dx;
+        x = x_new;
+        gs = g(x);
+        if dx.abs() <= 1e-15 * x.abs() {
+            break;
+        }
+    }
+
+    let r = r0 * gs[0] + eta0 * gs[1] + mu * gs[2];
+    let f = 1.0 - mu * gs[2] / r0;
+    let g = r0 * gs[1] + eta0 * gs[2];
+    let fdot = -mu * gs[1] / (r * r0);
+    let gdot = 1.0 - mu * gs[2] / r;
+    let p0 = *p;
+    let v0 = *v;
+    *p = p0 * f + v0 * g;
+    *v = p0 * fdot + v0 * gdot;
+}
+
+impl<F: ForceSolver> NBodyKernel for WisdomHolmanKernel<F> {
+    fn kernel(&self, state: PhysicsState, steps: u64, dt: u64) -> PhysicsState {
+        let n = state.p.len();
+        if n < 2 || steps == 0 {
+            return state;
+        }
+        assert!(
+            state.m[0] > 0.0,
+            "WisdomHolmanKernel needs a massive body 0 to orbit"
+        );
+        state.assert_newtonian_only("WisdomHolmanKernel", &[]);
+
+        let dtf = dt as f64 * util::UNIT_TIME;
+        let half = Vec3::splat(dtf / 2.0);
+        let mu = util::GRAVITY_CONSTANT * state.m[0];
+        let m = scale_m(&state.m, util::GRAVITY_CONSTANT);
+        let m0 = state.m[0];
+        let n_massive = state.n_massive();
+        let total_mass = state.total_mass();
+
+        // Democratic heliocentric coordinates: heliocentric positions and
+        // barycentric velocities of bodies 1..n, plus the center of mass.
+        let mut com = state.calc_center_of_mass();
+        let v_com = state.calc_momentum() / total_mass;
+        let mut q = vec![Vec3::ZERO; n];
+        let mut u = vec![Vec3::ZERO; n];
+        for i in 1..n {
+            q[i] = state.p[i] - state.p[0];
+            u[i] = state.v[i] - v_com;
+        }
+
+        let mut a = vec![Vec3::ZERO; n];
+        let jump = |q: &mut [Vec3], u: &[Vec3]| {
+            let mut s = Vec3::ZERO;
+            for i in 1..n {
+                s += state.m[i] * u[i];
+            }
+            let s = s * (dtf / 2.0 / m0);
+            for i in 1..n {
+                q[i] += s;
+            }
+        };
+
+        // The kicks: the mutual attraction of bodies 1..n, and the part of the
+        // attraction of body 0 that softening takes away from the Kepler drift.
+        let interactions = |q: &[Vec3], a: &mut [Vec3]| {
+            calc_a_massive(
+                &self.solver,
+                &q[1..],
+                &m[1..n_massive],
+                &state.softening,
+                &mut a[1..],
+            );
+            if state.softening != Softening::None {
+                for i in 1..n {
+                    let r2 = q[i].norm_squared();
+                    let g = state.softening.force(r2) - Softening::None.force(r2);
+                    a[i] = Vec3::mul_add(q[i], Vec3::splat(-mu * g), a[i]);
+                }
+            }
+        };
+
+        interactions(&q, &mut a);
+        advance(&mut u[1..], &a[1..], &half);
+        for step in 0..steps {
+            jump(&mut q, &u);
+            for i in 1..n {
+                kepler_drift(&mut q[i], &mut u[i], mu, dtf);
+            }
+            jump(&mut q, &u);
+            interactions(&q, &mut a);
+            let kick = if step + 1 == steps { half } else { half + half };
+            advance(&mut u[1..], &a[1..], &kick);
+        }
+        com += v_com * (steps as f64 * dtf);
+
+        let mut p = vec![Vec3::ZERO; n];
+        let mut v = vec![Vec3::ZERO; n];
+        let mut mq = Vec3::ZERO;
+        let mut mu_sum = Vec3::ZERO;
+        for i in 1..n {
+            mq += state.m[i] * q[i];
+            mu_sum += state.m[i] * u[i];
+        }
+        p[0] = com - mq / total_mass;
+        v[0] = v_com - mu_sum / m0;
+        for i in 1..n {
+            p[i] = q[i] + p[0];
+            v[i] = u[i] + v_com;
+        }
+        PhysicsState {
+            p: p,
+            v: v,
+            m: state.m,
+            r: state.r,
+            n_test: state.n_test,
+            t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        }
+    }
+}
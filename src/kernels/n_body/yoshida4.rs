@@ -0,0 +1,106 @@
+use std::marker::PhantomData;
+
+use crate::kernels::three_body::yoshida4_weights;
+use crate::{util, Scalar, Vec3, Vector};
+
+use super::*;
+
+/// 4th order Yoshida at the precision of `V`, like `VelVerletKernel`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Yoshida4Kernel<F: ForceSolver<V> = DirectSummation, V: Vector = Vec3> {
+    pub solver: F,
+    precision: PhantomData<V>,
+}
+
+impl<F: ForceSolver<V>, V: Vector> Yoshida4Kernel<F, V> {
+    pub fn new(solver: F) -> Self {
+        Yoshida4Kernel {
+            solver,
+            precision: PhantomData,
+        }
+    }
+}
+
+impl<F: ForceSolver<V>, V: Vector> NBodyKernel for Yoshida4Kernel<F, V> {
+    fn kernel(&self, state: PhysicsState, steps: u64, dt: u64) -> PhysicsState {
+        state.assert_newtonian_only(
+            "Yoshida4Kernel",
+            &[Extension::External, Extension::ForceLaw],
+        );
+        let n = state.p.len();
+        let (length, time) = units(&state);
+        let dtf = V::Scalar::from_f64(dt as f64 * util::UNIT_TIME / time);
+        let a_scale = time * time / length;
+
+        let mu = scale_m(&state.m, util::GRAVITY_CONSTANT);
+        let m: Vec<V::Scalar> = scale_m(&mu, time * time / (length * length * length))
+            .iter()
+            .map(|&m| V::Scalar::from_f64(m))
+            .collect();
+        let n_massive = state.n_massive();
+        let softening = state.softening.in_units(length);
+        let force_law = &state.force_law;
+        let external = &state.external;
+        let calc_a = |p: &[V], a: &mut [V]| {
+            match force_law {
+                None => calc_a_massive(&self.solver, p, &m[..n_massive], &softening, a),
+                Some(force_law) => calc_a_force_law_in_units(
+                    p,
+                    length,
+                    &mu,
+                    n_massive,
+                    force_law.as_ref(),
+                    a_scale,
+                    a,
+                ),
+            }
+            add_external(p, length, external, a_scale, a);
+        };
+
+        let (w1, w0) = yoshida4_weights::<V::Scalar>();
+        let half = V::Scalar::from_f64(0.5);
+        let c1 = V::splat(w1 * half * dtf);
+        let c2 = V::splat((w0 + w1) * half * dtf);
+        let c3 = c2;
+        let c4 = c1;
+
+        let d1 = V::splat(w1 * dtf);
+        let d2 = V::splat(w0 * dtf);
+        let d3 = d1;
+
+        let mut p = to_units::<V>(&state.p, length);
+        let mut v = to_units::<V>(&state.v, length / time);
+        let mut a = vec![V::ZERO; n];
+
+        for _ in 0..steps {
+            advance(&mut p, &v, &c1);
+            calc_a(&p, &mut a);
+            advance(&mut v, &a, &d1);
+
+            advance(&mut p, &v, &c2);
+            calc_a(&p, &mut a);
+            advance(&mut v, &a, &d2);
+
+            advance(&mut p, &v, &c3);
+            calc_a(&p, &mut a);
+            advance(&mut v, &a, &d3);
+
+            advance(&mut p, &v, &c4);
+        }
+
+        PhysicsState {
+            p: from_units(&p, length),
+            v: from_units(&v, length / time),
+            m: state.m,
+            r: state.r,
+            n_test: state.n_test,
+            t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::util;
+use crate::Vec3;
+
+/// Post-Newtonian corrections to the accelerations. Like the softening, they
+/// are a property of the simulated system and live in the state.
+///
+/// The corrections depend on the velocities, so only the kernels integrating
+/// the full first order system `(p, v)' = (v, a(p, v))` take them into account:
+/// the three-body RK4, Dormand–Prince, Bulirsch–Stoer and IAS15 kernels, and
+/// the N-body RK4 kernel. The others assume accelerations depending on the
+/// positions only. The corrections are never softened, and the energy
+/// diagnostics stay Newtonian.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PostNewtonian {
+    /// 1PN Einstein–Infeld–Hoffmann accelerations, in the form used for the
+    /// JPL ephemerides (Newhall, Standish & Williams 1983). Conservative, with
+    /// the perihelion advance `6 pi G M / (c^2 a (1 - e^2))` per orbit.
+    pub pn1: bool,
+    /// 2.5PN radiation reaction of each pair (Lincoln & Will 1990), which
+    /// drains the orbital energy at the rate of the quadrupole formula.
+    pub pn2_5: bool,
+}
+
+impl PostNewtonian {
+    pub fn is_enabled(&self) -> bool {
+        return self.pn1 || self.pn2_5;
+    }
+
+    /// Adds the enabled corrections to `a`, which holds the Newtonian
+    /// accelerations on entry. `mu` holds the masses times `G`.
+    pub fn add_a(&self, p: &[Vec3], v: &[Vec3], mu: &[f64], a: &mut [Vec3]) {
+        let n = p.len();
+        let c2 = util::SPEED_LIGHT * util::SPEED_LIGHT;
+
+        if self.pn1 {
+            let a_newton = a.to_vec();
+            // Newtonian potential at each body, positive.
+            let mut phi = vec![0.0; n];
+            for i in 0..n {
+                for j in (i + 1)..n {
+                    let r = (p[j] - p[i]).norm();
+                    phi[i] += mu[j] / r;
+                    phi[j] += mu[i] / r;
+                }
+            }
+            for i in 0..n {
+                let v2_i = v[i].norm_squared();
+                let mut da = Vec3::ZERO;
+                for j in 0..n {
+                    if j == i || mu[j] == 0.0 {
+                        continue;
+                    }
+                    let d = p[j] - p[i];
+                    let r2 = d.norm_squared();
+                    let r = r2.sqrt();
+                    let mr3 = mu[j] / (r2 * r);
+                    let nv_j = (d * v[j]).reduce_add() / r;
+                    let coefficient = -4.0 * phi[i] - phi[j] + v2_i + 2.0 * v[j].norm_squared()
+                        - 4.0 * (v[i] * v[j]).reduce_add()
+                        - 1.5 * nv_j * nv_j
+                        + 0.5 * (d * a_newton[j]).reduce_add();
+                    let w = v[i] - v[j];
+                    let dw = -(d * (4.0 * v[i] - 3.0 * v[j])).reduce_add();
+                    da = Vec3::mul_add(d, Vec3::splat(mr3 * coefficient), da);
+                    da = Vec3::mul_add(w, Vec3::splat(mr3 * dw), da);
+                    da = Vec3::mul_add(a_newton[j], Vec3::splat(3.5 * mu[j] / r), da);
+                }
+                a[i] = Vec3::mul_add(da, Vec3::splat(1.0 / c2), a[i]);
+            }
+        }
+
+        if self.pn2_5 {
+            let c5 = c2 * c2 * util::SPEED_LIGHT;
+            for i in 0..n {
+                for j in (i + 1)..n {
+                    if mu[i] == 0.0 || mu[j] == 0.0 {
+                        continue;
+                    }
+                    let mu_t = mu[i] + mu[j];
+                    let eta = mu[i] * mu[j] / (mu_t * mu_t);
+                    let x = p[i] - p[j];
+                    let w = v[i] - v[j];
+                    let r = x.norm();
+                    let n_ij = x / r;
+                    let rdot = (n_ij * w).reduce_add();
+                    let w2 = w.norm_squared();
+                    let u = mu_t / r;
+                    let k = 1.6 * eta * u / c5;
+                    let a_n = -k * rdot * (18.0 * w2 + 2.0 / 3.0 * u - 25.0 * rdot * rdot);
+                    let b_w = k * (6.0 * w2 - 2.0 * u - 15.0 * rdot * rdot);
+                    // Relative acceleration, split so that momentum is kept.
+                    let a_rel = (n_ij * a_n + w * b_w) * (-u / r);
+                    a[i] = Vec3::mul_add(a_rel, Vec3::splat(mu[j] / mu_t), a[i]);
+                    a[j] = Vec3::mul_neg_add(a_rel, Vec3::splat(mu[i] / mu_t), a[j]);
+                }
+            }
+        }
+    }
+}
@@ -1,14 +1,35 @@
+use std::sync::Arc;
+
 use crate::*;
 
-use super::PhysicsState;
+use super::{
+    advance_time, assert_newtonian_only, calc_a_force_law, external_acceleration, find_root, Event,
+    EventFunction, Extension, ExternalPotential, ForceLaw, PhysicsState, PostNewtonian, Softening,
+};
 
+mod bulirsch_stoer;
+mod chain;
+mod composition;
+mod dense;
+mod dormand_prince;
+mod ias15;
 mod rk4;
+mod splitting;
 mod symplectic_euler;
+mod time_transformed;
 mod vel_verlet;
 mod yoshida4;
 
+pub use bulirsch_stoer::*;
+pub use chain::*;
+pub use composition::*;
+pub use dense::*;
+pub use dormand_prince::*;
+pub use ias15::*;
 pub use rk4::*;
+pub use splitting::*;
 pub use symplectic_euler::*;
+pub use time_transformed::*;
 pub use vel_verlet::*;
 pub use yoshida4::*;
 
@@ -17,6 +38,30 @@ pub struct ThreeBodyState {
     pub v: [Vec3; 3],
     pub m: [f64; 3],
     pub t: u64,
+    pub t_frac: f64,
+    pub softening: Softening,
+    pub post_newtonian: PostNewtonian,
+    pub external: Vec<Arc<dyn ExternalPotential>>,
+    pub force_law: Option<Arc<dyn ForceLaw>>,
+    pub events: Vec<Arc<dyn EventFunction>>,
+}
+
+impl ThreeBodyState {
+    /// Time in seconds.
+    pub fn time(&self) -> f64 {
+        return self.t as f64 * util::UNIT_TIME + self.t_frac;
+    }
+
+    /// As `PhysicsState::assert_newtonian_only`.
+    pub fn assert_newtonian_only(&self, kernel: &str, handled: &[Extension]) {
+        assert_newtonian_only(
+            kernel,
+            handled,
+            &self.post_newtonian,
+            &self.external,
+            &self.force_law,
+        );
+    }
 }
 
 impl From<&PhysicsState> for ThreeBodyState {
@@ -29,6 +74,12 @@ impl From<&PhysicsState> for ThreeBodyState {
             v: [state.v[0], state.v[1], state.v[2]],
             m: [state.m[0], state.m[1], state.m[2]],
             t: state.t,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external.clone(),
+            force_law: state.force_law.clone(),
+            events: state.events.clone(),
         };
     }
 }
@@ -39,7 +90,15 @@ impl From<&ThreeBodyState> for PhysicsState {
             p: vec![state.p[0], state.p[1], state.p[2]],
             v: vec![state.v[0], state.v[1], state.v[2]],
             m: vec![state.m[0], state.m[1], state.m[2]],
+            r: vec![0.0; 3],
+            n_test: 0,
             t: state.t,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external.clone(),
+            force_law: state.force_law.clone(),
+            events: state.events.clone(),
         };
     }
 }
@@ -48,24 +107,367 @@ pub trait ThreeBodyKernel {
     #[must_use]
     fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState;
 
-    // #[must_use]
-    // fn step_length(state: ThreeBodyState, error: f64) -> u64;
-
     fn simulate(state: &mut PhysicsState, batch_count: u64, step_count: u64, dt: u64) {
         let mut state1 = ThreeBodyState::from(&*state);
         for _ in 0..batch_count {
             state1 = Self::kernel(state1, step_count, dt);
         }
-        *state = PhysicsState::from(&state1);
+        write_back(state, &state1);
+    }
+
+    /// Like `kernel`, but also returns a dense output of the trajectory. By
+    /// default the kernel is run one step at a time, and each step is
+    /// interpolated by cubic Hermite polynomials from the positions,
+    /// velocities and position dependent accelerations at both ends.
+    #[must_use]
+    fn kernel_dense(state: ThreeBodyState, steps: u64, dt: u64) -> (ThreeBodyState, DenseOutput) {
+        let forces = Forces::new(&state);
+        let derivative = |state: &ThreeBodyState| Phase {
+            p: state.v,
+            v: forces.calc_a(&state.p),
+        };
+        let mut dense = DenseOutput::default();
+        let mut state = state;
+        let mut f = derivative(&state);
+        for _ in 0..steps {
+            let t0 = state.time();
+            let y0 = Phase {
+                p: state.p,
+                v: state.v,
+            };
+            state = Self::kernel(state, 1, dt);
+            let y1 = Phase {
+                p: state.p,
+                v: state.v,
+            };
+            let f1 = derivative(&state);
+            dense.push_hermite(t0, state.time() - t0, &y0, &f, &y1, &f1);
+            f = f1;
+        }
+        return (state, dense);
+    }
+
+    fn simulate_dense(state: &mut PhysicsState, steps: u64, dt: u64) -> DenseOutput {
+        let (state1, dense) = Self::kernel_dense(ThreeBodyState::from(&*state), steps, dt);
+        write_back(state, &state1);
+        return dense;
+    }
+
+    /// Like `kernel`, but also returns the events of the state located on the
+    /// dense output of the run. At a terminal event the state is moved back to
+    /// the event, and the events after it are dropped.
+    #[must_use]
+    fn kernel_events(state: ThreeBodyState, steps: u64, dt: u64) -> (ThreeBodyState, Vec<Event>) {
+        let (t, t_frac, t0) = (state.t, state.t_frac, state.time());
+        let (mut state, dense) = Self::kernel_dense(state, steps, dt);
+        let log = dense.find_events(&state.events);
+        if let Some(event) = log
+            .last()
+            .filter(|event| state.events[event.event].terminal())
+        {
+            (state.t, state.t_frac) = advance_time(t, t_frac, event.t - t0);
+            state.p = [event.p[0], event.p[1], event.p[2]];
+            state.v = [event.v[0], event.v[1], event.v[2]];
+        }
+        return (state, log);
+    }
+
+    fn simulate_events(state: &mut PhysicsState, steps: u64, dt: u64) -> Vec<Event> {
+        let (state1, log) = Self::kernel_events(ThreeBodyState::from(&*state), steps, dt);
+        write_back(state, &state1);
+        return log;
+    }
+}
+
+/// Error tolerances for the adaptive kernels. The local error of each body's
+/// position (in m) and velocity (in m/s) is kept below
+/// `atol + rtol * |value|`.
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerance {
+    pub atol: f64,
+    pub rtol: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance {
+            atol: 1e-6,
+            rtol: 1e-12,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StepStats {
+    pub accepted: u64,
+    pub rejected: u64,
+}
+
+pub trait AdaptiveThreeBodyKernel {
+    /// Integrates for `duration` ticks with error-controlled internal steps,
+    /// landing exactly on the end time. Panics when the step size underflows,
+    /// as at a collision.
+    #[must_use]
+    fn kernel_adaptive(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+    ) -> (ThreeBodyState, StepStats);
+
+    fn simulate_adaptive(
+        state: &mut PhysicsState,
+        duration: u64,
+        tolerance: Tolerance,
+    ) -> StepStats {
+        let (state1, stats) =
+            Self::kernel_adaptive(ThreeBodyState::from(&*state), duration, tolerance);
+        write_back(state, &state1);
+        return stats;
+    }
+
+    /// Like `kernel_adaptive`, but also returns a dense output of the
+    /// trajectory over the accepted steps.
+    #[must_use]
+    fn kernel_adaptive_dense(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+    ) -> (ThreeBodyState, StepStats, DenseOutput);
+
+    fn simulate_adaptive_dense(
+        state: &mut PhysicsState,
+        duration: u64,
+        tolerance: Tolerance,
+    ) -> (StepStats, DenseOutput) {
+        let (state1, stats, dense) =
+            Self::kernel_adaptive_dense(ThreeBodyState::from(&*state), duration, tolerance);
+        write_back(state, &state1);
+        return (stats, dense);
+    }
+}
+
+/// What the accelerations depend on besides the positions and velocities:
+/// the masses times `G`, both splatted and as scalars, and the softening,
+/// post-Newtonian terms, external potentials and force law of the state.
+///
+/// The positions and velocities are in the units `(length, time)`, meters
+/// and seconds for the `f64` kernels, and the pairwise gravity is evaluated
+/// at the precision of `V`. The force law, the external potentials and the
+/// post-Newtonian terms take the positions and velocities in SI units and in
+/// `f64`.
+struct Forces<V: Vector = Vec3> {
+    m: [V; 3],
+    mu: [f64; 3],
+    length: f64,
+    time: f64,
+    /// Factor from SI accelerations to the kernel's, including the `scale`
+    /// the kernel folds into them.
+    a_scale: f64,
+    softening: Softening,
+    post_newtonian: PostNewtonian,
+    external: Vec<Arc<dyn ExternalPotential>>,
+    force_law: Option<Arc<dyn ForceLaw>>,
+}
+
+impl Forces {
+    fn new(state: &ThreeBodyState) -> Forces {
+        return Forces::scaled(state, 1.0);
+    }
+
+    /// Forces whose position dependent accelerations are multiplied by
+    /// `scale`, for the kernels folding the time step into them.
+    fn scaled(state: &ThreeBodyState, scale: f64) -> Forces {
+        return Forces::in_units(state, (1.0, 1.0), scale);
+    }
+}
+
+impl<V: Vector> Forces<V> {
+    /// Forces in the units `(length, time)`, as `scaled` otherwise.
+    fn in_units(state: &ThreeBodyState, (length, time): (f64, f64), scale: f64) -> Forces<V> {
+        let mu = state.m.map(|m| m * util::GRAVITY_CONSTANT);
+        let mu_scale = time * time / (length * length * length);
+        Forces {
+            m: mu.map(|mu| V::splat(V::Scalar::from_f64(mu * mu_scale * scale))),
+            mu: mu,
+            length: length,
+            time: time,
+            a_scale: scale * (time * time / length),
+            softening: state.softening.in_units(length),
+            post_newtonian: state.post_newtonian,
+            external: state.external.clone(),
+            force_law: state.force_law.clone(),
+        }
+    }
+
+    /// Accelerations depending on the positions only.
+    #[inline(always)]
+    fn calc_a(&self, p: &[V; 3]) -> [V; 3] {
+        let mut a = match &self.force_law {
+            None => calc_a(p, &self.m, &self.softening),
+            Some(force_law) => {
+                let p = p.map(|p| p.to_vec3() * self.length);
+                let mut a = [Vec3::ZERO; 3];
+                calc_a_force_law(&p, &self.mu, 3, force_law.as_ref(), self.a_scale, &mut a);
+                a.map(V::from_vec3)
+            }
+        };
+        if !self.external.is_empty() {
+            let scale = V::splat(V::Scalar::from_f64(self.a_scale));
+            for i in 0..3 {
+                let a_i = external_acceleration(&self.external, &(p[i].to_vec3() * self.length));
+                a[i] = V::mul_add(V::from_vec3(a_i), scale, a[i]);
+            }
+        }
+        return a;
+    }
+
+    /// Accelerations including the velocity dependent post-Newtonian terms.
+    #[inline(always)]
+    fn calc_a_v(&self, p: &[V; 3], v: &[V; 3]) -> [V; 3] {
+        let mut a = self.calc_a(p);
+        if self.post_newtonian.is_enabled() {
+            let p = p.map(|p| p.to_vec3() * self.length);
+            let v = v.map(|v| v.to_vec3() * (self.length / self.time));
+            let mut a_si = a.map(|a| a.to_vec3() / self.a_scale);
+            self.post_newtonian.add_a(&p, &v, &self.mu, &mut a_si);
+            a = a_si.map(|a| V::from_vec3(a * self.a_scale));
+        }
+        return a;
+    }
+}
+
+/// Replaces `state` by the result `state1` of a three-body kernel. The radii
+/// and the test particles play no part in the three-body kernels, whose
+/// massless bodies need no special treatment, so they are kept as they were.
+fn write_back(state: &mut PhysicsState, state1: &ThreeBodyState) {
+    let r = std::mem::take(&mut state.r);
+    let n_test = state.n_test;
+    *state = PhysicsState::from(state1);
+    state.r = r;
+    state.n_test = n_test;
+}
+
+/// Position and velocity of all three bodies, or their time derivatives.
+#[derive(Clone, Copy, Debug)]
+struct Phase<V: Vector = Vec3> {
+    p: [V; 3],
+    v: [V; 3],
+}
+
+impl<V: Vector> Phase<V> {
+    const ZERO: Phase<V> = Phase {
+        p: [V::ZERO; 3],
+        v: [V::ZERO; 3],
+    };
+
+    #[inline(always)]
+    fn derivative(&self, forces: &Forces<V>) -> Phase<V> {
+        Phase {
+            p: self.v,
+            v: forces.calc_a_v(&self.p, &self.v),
+        }
+    }
+
+    #[inline(always)]
+    fn advance(&self, k: &Phase<V>, c: V::Scalar) -> Phase<V> {
+        let c = V::splat(c);
+        Phase {
+            p: advance(&self.p, &k.p, &c),
+            v: advance(&self.v, &k.v, &c),
+        }
+    }
+
+    /// Positions and velocities of a state in the units `(length, time)`.
+    fn in_units(state: &ThreeBodyState, (length, time): (f64, f64)) -> Phase<V> {
+        Phase {
+            p: state.p.map(|p| V::from_vec3(p / length)),
+            v: state.v.map(|v| V::from_vec3(v / (length / time))),
+        }
+    }
+
+    /// Back from the units `(length, time)` to SI units, also for the
+    /// coefficients of a dense output.
+    fn in_si(&self, (length, time): (f64, f64)) -> Phase {
+        Phase {
+            p: self.p.map(|p| p.to_vec3() * length),
+            v: self.v.map(|v| v.to_vec3() * (length / time)),
+        }
     }
 }
 
+/// Powers of two near the largest distance from the origin and the dynamical
+/// time `sqrt(r^3 / mu)` of the heaviest body at that distance, the units of
+/// the kernels generic over precision. Scaling by them is exact at any
+/// precision, so the `f64` kernels give the same results as in SI units,
+/// and it keeps the cubes of distances and the masses times `G` within the
+/// range of `f32`.
+pub fn natural_scales(p: &[Vec3], mu: &[f64]) -> (f64, f64) {
+    let power_of_two = |x: f64| {
+        if x > 0.0 && x.is_finite() {
+            2f64.powi(x.log2().round() as i32)
+        } else {
+            1.0
+        }
+    };
+    let r = p.iter().map(|p| p.norm()).fold(0.0, f64::max);
+    let mu = mu.iter().fold(0.0, |a: f64, &b| a.max(b));
+    let length = power_of_two(r);
+    let time = power_of_two((length * length * length / mu).sqrt());
+    return (length, time);
+}
+
+/// `natural_scales` of a state.
+fn units(state: &ThreeBodyState) -> (f64, f64) {
+    return natural_scales(&state.p, &state.m.map(|m| m * util::GRAVITY_CONSTANT));
+}
+
+/// Orbital timescale `sqrt(r^3 / (G M))` of the closest pair with mass, for
+/// picking the first step size.
+fn dynamical_time(p: &[Vec3; 3], m: &[f64; 3]) -> f64 {
+    let mut tau = f64::INFINITY;
+    for i in 0..3 {
+        for j in (i + 1)..3 {
+            let mu = util::GRAVITY_CONSTANT * (m[i] + m[j]);
+            if mu > 0.0 {
+                let r = (p[i] - p[j]).norm();
+                tau = tau.min((r * r * r / mu).sqrt());
+            }
+        }
+    }
+    return tau;
+}
+
+/// Stops an adaptive kernel whose step `h` at the time `t` since its start
+/// has shrunk below `16 eps |t|`, where `t + h` no longer advances the time,
+/// or has become NaN, as at a collision, instead of looping forever.
+fn check_step(kernel: &str, h: f64, t: f64, t0: f64) {
+    if h.is_nan() || h <= 16.0 * f64::EPSILON * t.abs().max(f64::MIN_POSITIVE) {
+        panic!(
+            "{}: step size underflow at t = {:e} s, the bodies collide or the tolerance is too tight",
+            kernel,
+            t0 + t
+        );
+    }
+}
+
+/// Weighted RMS norm of the error estimate `err` of a step from `y0` to `y1`,
+/// `<= 1` means accept.
+fn error_norm(y0: &Phase, y1: &Phase, err: &Phase, tolerance: &Tolerance) -> f64 {
+    let mut sum = 0.0;
+    for i in 0..3 {
+        let sc_p = tolerance.atol + tolerance.rtol * y0.p[i].norm().max(y1.p[i].norm());
+        let sc_v = tolerance.atol + tolerance.rtol * y0.v[i].norm().max(y1.v[i].norm());
+        sum += (err.p[i].norm() / sc_p).powi(2) + (err.v[i].norm() / sc_v).powi(2);
+    }
+    return (sum / 6.0).sqrt();
+}
+
 #[inline(always)]
 #[must_use]
-pub fn calc_a(p: &[Vec3; 3], m: &[Vec3; 3]) -> [Vec3; 3] {
-    let r01 = Vec3::calc_r(&p[0], &p[1]);
-    let r12 = Vec3::calc_r(&p[1], &p[2]);
-    let r20 = Vec3::calc_r(&p[2], &p[0]);
+pub fn calc_a<V: Vector>(p: &[V; 3], m: &[V; 3], softening: &Softening) -> [V; 3] {
+    let r01 = softening.calc_r(&p[0], &p[1]);
+    let r12 = softening.calc_r(&p[1], &p[2]);
+    let r20 = softening.calc_r(&p[2], &p[0]);
 
     let a0 = r01 * m[1] - r20 * m[2];
     let a1 = r12 * m[2] - r01 * m[0];
@@ -77,14 +479,14 @@ pub fn calc_a(p: &[Vec3; 3], m: &[Vec3; 3]) -> [Vec3; 3] {
 #[inline(always)]
 #[must_use]
 #[allow(dead_code)]
-fn add(a: &[Vec3; 3], b: &[Vec3; 3]) -> [Vec3; 3] {
+fn add<V: Vector>(a: &[V; 3], b: &[V; 3]) -> [V; 3] {
     [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
 }
 
 #[inline(always)]
 #[must_use]
 #[allow(dead_code)]
-fn sub(a: &[Vec3; 3], b: &[Vec3; 3]) -> [Vec3; 3] {
+fn sub<V: Vector>(a: &[V; 3], b: &[V; 3]) -> [V; 3] {
     [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
 }
 
@@ -95,17 +497,29 @@ fn mul(a: &[Vec3; 3], b: &[Vec3; 3]) -> [Vec3; 3] {
     [a[0] * b[0], a[1] * b[1], a[2] * b[2]]
 }
 
-fn mul_same(a: &[Vec3; 3], b: &Vec3) -> [Vec3; 3] {
-    [a[0] * b, a[1] * b, a[2] * b]
+fn mul_same<V: Vector>(a: &[V; 3], b: &V) -> [V; 3] {
+    [a[0] * *b, a[1] * *b, a[2] * *b]
+}
+
+/// Kahan summation of `x += dx`, with the running compensation in `c`, so
+/// that `x - c` is the sum to about twice the precision.
+#[inline(always)]
+fn compensated_add(x: &mut [Vec3; 3], c: &mut [Vec3; 3], dx: &[Vec3; 3]) {
+    for i in 0..3 {
+        let y = dx[i] - c[i];
+        let t = x[i] + y;
+        c[i] = (t - x[i]) - y;
+        x[i] = t;
+    }
 }
 
 #[inline(always)]
 #[must_use]
 #[allow(dead_code)]
-fn advance(a: &[Vec3; 3], b: &[Vec3; 3], c: &Vec3) -> [Vec3; 3] {
+fn advance<V: Vector>(a: &[V; 3], b: &[V; 3], c: &V) -> [V; 3] {
     [
-        Vec3::mul_add(b[0], *c, a[0]),
-        Vec3::mul_add(b[1], *c, a[1]),
-        Vec3::mul_add(b[2], *c, a[2]),
+        V::mul_add(b[0], *c, a[0]),
+        V::mul_add(b[1], *c, a[1]),
+        V::mul_add(b[2], *c, a[2]),
     ]
 }
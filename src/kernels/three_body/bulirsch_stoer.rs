@@ -0,0 +1,283 @@
+use crate::util;
+
+use super::*;
+
+const MAX_COLUMNS: usize = 8;
+// Columns used per step by the fixed step kernel, giving 8th order.
+const FIXED_COLUMNS: usize = 4;
+const SAFETY: f64 = 0.94;
+const MIN_FACTOR: f64 = 0.2;
+const MAX_FACTOR: f64 = 4.0;
+
+/// Gragg–Bulirsch–Stoer extrapolation.
+///
+/// A step of length `h` is taken with Gragg's modified midpoint rule using
+/// `n = 2, 4, 6, ...` substeps, whose error expands in even powers of
+/// `h / n`, and the results are Richardson extrapolated to `n -> infinity`.
+/// With `j + 1` columns the result is of order `2 (j + 1)`.
+///
+/// As a `ThreeBodyKernel` every step uses the same number of columns, so its
+/// order shows in the convergence tests. As an `AdaptiveThreeBodyKernel` the
+/// step size and number of columns are controlled together (Hairer, Nørsett &
+/// Wanner, II.9), picking the column with the least work per unit time.
+pub struct BulirschStoerKernel;
+
+/// Result of `BulirschStoerKernel::step`: the most extrapolated column
+/// computed, which is the new state if `accepted`, and the optimal step size
+/// for each column.
+struct Step {
+    y: Phase,
+    column: usize,
+    accepted: bool,
+    h_opt: [f64; MAX_COLUMNS],
+}
+
+impl BulirschStoerKernel {
+    /// Gragg's modified midpoint rule over `h` in `n` substeps, starting from
+    /// the derivative `f0` at `y0`.
+    fn midpoint(y0: &Phase, f0: &Phase, forces: &Forces, h: f64, n: usize) -> Phase {
+        let hs = h / n as f64;
+        let mut z0 = *y0;
+        let mut z1 = y0.advance(f0, hs);
+        for _ in 1..n {
+            let z2 = z0.advance(&z1.derivative(forces), 2.0 * hs);
+            z0 = z1;
+            z1 = z2;
+        }
+        return z1;
+    }
+
+    /// Computes row `j` of the extrapolation tableau into `row`, which holds
+    /// row `j - 1` on entry.
+    fn extrapolate(row: &mut [Phase; MAX_COLUMNS], y: Phase, j: usize) {
+        let mut prev = y;
+        for l in 1..=j {
+            let ratio = ((j + 1) as f64 / (j - l + 1) as f64).powi(2);
+            let diff = prev.advance(&row[l - 1], -1.0);
+            row[l - 1] = prev;
+            prev = prev.advance(&diff, 1.0 / (ratio - 1.0));
+        }
+        row[j] = prev;
+    }
+
+    /// One step of length `h`, accepted as soon as one of the columns `k - 1`
+    /// to `k + 1` meets the tolerance.
+    fn step(
+        y: &Phase,
+        f0: &Phase,
+        forces: &Forces,
+        h: f64,
+        k: usize,
+        tolerance: &Tolerance,
+    ) -> Step {
+        let mut h_opt = [0.0; MAX_COLUMNS];
+        let mut row = [Phase::ZERO; MAX_COLUMNS];
+        for j in 0..=(k + 1) {
+            Self::extrapolate(&mut row, Self::midpoint(y, f0, forces, h, 2 * (j + 1)), j);
+            if j >= 1 {
+                let err = error_norm(y, &row[j], &row[j].advance(&row[j - 1], -1.0), tolerance);
+                let factor = if err == 0.0 {
+                    MAX_FACTOR
+                } else {
+                    (SAFETY * (0.65 / err).powf(1.0 / (2 * j + 1) as f64))
+                        .clamp(MIN_FACTOR, MAX_FACTOR)
+                };
+                h_opt[j] = h * factor;
+                if j + 1 >= k && (err <= 1.0 || j == k + 1) {
+                    return Step {
+                        y: row[j],
+                        column: j,
+                        accepted: err <= 1.0,
+                        h_opt: h_opt,
+                    };
+                }
+            }
+        }
+        unreachable!();
+    }
+
+    fn integrate(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+        mut dense: Option<&mut DenseOutput>,
+    ) -> (ThreeBodyState, StepStats) {
+        let forces = Forces::new(&state);
+        let t0 = state.time();
+        let t_end = duration as f64 * util::UNIT_TIME;
+        // Derivative evaluations per step up to each column.
+        let cost: [f64; MAX_COLUMNS] = std::array::from_fn(|j| ((j + 1) * (j + 1) + 1) as f64);
+
+        let mut stats = StepStats::default();
+        let mut y = Phase {
+            p: state.p,
+            v: state.v,
+        };
+        let mut t = 0.0;
+        let mut h = 0.01 * dynamical_time(&state.p, &state.m);
+        let mut k = 4;
+        let mut last_rejected = false;
+        // The steps span a good fraction of an orbit, far too long for a cubic
+        // interpolation, whose relative error over `theta` radians of the
+        // orbit is about `theta^4 / 384`. As in the chain kernel, dense output
+        // segments are kept to the `theta` meeting the tolerance, the dynamical
+        // time being the time per radian of a circular orbit.
+        let theta = (384.0 * tolerance.rtol).powf(0.25).clamp(1e-3, 0.1);
+
+        while t < t_end {
+            check_step("BulirschStoerKernel", h, t, t0);
+            let last = t + h >= t_end;
+            if last {
+                h = t_end - t;
+            }
+
+            let f0 = y.derivative(&forces);
+            let step = Self::step(&y, &f0, &forces, h, k, &tolerance);
+            let h_opt = step.h_opt;
+            if !step.accepted {
+                stats.rejected += 1;
+                h = h_opt[k].min(h * 0.5);
+                last_rejected = true;
+                continue;
+            }
+            let j = step.column;
+            // Intermediate points of the dense output, by shorter steps from
+            // the same start. These are at least as accurate as the accepted
+            // one, but are checked all the same.
+            let n = match dense {
+                Some(_) => {
+                    let tau =
+                        dynamical_time(&y.p, &state.m).min(dynamical_time(&step.y.p, &state.m));
+                    (h / (theta * tau)).ceil().max(1.0) as usize
+                }
+                None => 1,
+            };
+            let mut points: Vec<Option<Phase>> = (1..n)
+                .map(|i| {
+                    let hs = h * i as f64 / n as f64;
+                    let step = Self::step(
+                        &y,
+                        &f0,
+                        &forces,
+                        hs,
+                        j.clamp(2, MAX_COLUMNS - 2),
+                        &tolerance,
+                    );
+                    return if step.accepted { Some(step.y) } else { None };
+                })
+                .collect();
+            points.push(Some(step.y));
+            let Some(points) = points.into_iter().collect::<Option<Vec<Phase>>>() else {
+                // A shorter step failed where the full one passed, as can
+                // happen with a tolerance near roundoff, so retry shorter.
+                stats.rejected += 1;
+                h *= 0.5;
+                last_rejected = true;
+                continue;
+            };
+            stats.accepted += 1;
+            if let Some(dense) = &mut dense {
+                let mut t_i = t;
+                let mut y_i = y;
+                let mut f_i = f0;
+                for (i, y1) in points.iter().enumerate() {
+                    let t1 = t + h * (i + 1) as f64 / n as f64;
+                    let f1 = y1.derivative(&forces);
+                    dense.push_hermite(t0 + t_i, t1 - t_i, &y_i, &f_i, y1, &f1);
+                    t_i = t1;
+                    y_i = *y1;
+                    f_i = f1;
+                }
+            }
+            t = if last { t_end } else { t + h };
+            y = step.y;
+
+            // Pick the next column by the work per unit time.
+            let work = |j: usize| cost[j] / h_opt[j];
+            let (k_new, h_new) = if j > 1 && work(j - 1) < 0.8 * work(j) {
+                (j - 1, h_opt[j - 1])
+            } else if j > 1 && j + 1 < MAX_COLUMNS && work(j) < 0.9 * work(j - 1) {
+                (j + 1, h_opt[j] * cost[j + 1] / cost[j])
+            } else {
+                (j, h_opt[j])
+            };
+            k = k_new.clamp(2, MAX_COLUMNS - 2);
+            h = if last_rejected { h_new.min(h) } else { h_new };
+            last_rejected = false;
+        }
+
+        let state = ThreeBodyState {
+            p: y.p,
+            v: y.v,
+            m: state.m,
+            t: state.t + duration,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        };
+        return (state, stats);
+    }
+}
+
+impl AdaptiveThreeBodyKernel for BulirschStoerKernel {
+    fn kernel_adaptive(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+    ) -> (ThreeBodyState, StepStats) {
+        Self::integrate(state, duration, tolerance, None)
+    }
+
+    /// Interpolates each step by cubic Hermite polynomials over segments short
+    /// enough to meet the tolerance, each ending at a shorter step from the
+    /// start of the step, so a dense run takes an extra step per segment.
+    fn kernel_adaptive_dense(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+    ) -> (ThreeBodyState, StepStats, DenseOutput) {
+        let mut dense = DenseOutput::default();
+        let (state, stats) = Self::integrate(state, duration, tolerance, Some(&mut dense));
+        return (state, stats, dense);
+    }
+}
+
+impl ThreeBodyKernel for BulirschStoerKernel {
+    fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
+        let forces = Forces::new(&state);
+        let h = dt as f64 * util::UNIT_TIME;
+
+        let mut y = Phase {
+            p: state.p,
+            v: state.v,
+        };
+        let mut row = [Phase::ZERO; MAX_COLUMNS];
+        for _ in 0..steps {
+            let f0 = y.derivative(&forces);
+            for j in 0..FIXED_COLUMNS {
+                Self::extrapolate(
+                    &mut row,
+                    Self::midpoint(&y, &f0, &forces, h, 2 * (j + 1)),
+                    j,
+                );
+            }
+            y = row[FIXED_COLUMNS - 1];
+        }
+
+        ThreeBodyState {
+            p: y.p,
+            v: y.v,
+            m: state.m,
+            t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        }
+    }
+}
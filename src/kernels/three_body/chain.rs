@@ -0,0 +1,509 @@
+use crate::util;
+use crate::Vec3;
+
+use super::*;
+
+const MAX_COLUMNS: usize = 8;
+const SAFETY: f64 = 0.94;
+const MIN_FACTOR: f64 = 0.2;
+const MAX_FACTOR: f64 = 4.0;
+const MAX_ITERATIONS: usize = 20;
+// Pairs whose mass product is below this fraction of the squared mean mass,
+// e.g. a test particle and a star, are regularized by the TTL term instead.
+pub(crate) const SMALL_MASS: f64 = 1e-3;
+const TOLERANCE: Tolerance = Tolerance {
+    atol: 0.0,
+    rtol: 1e-13,
+};
+
+// The pairs of chain positions, whose separations are `x[0]`, `x[1]` and
+// `x[0] + x[1]`.
+const PAIRS: [(usize, usize); 3] = [(0, 1), (1, 2), (0, 2)];
+
+/// Mikkola–Aarseth chain regularization (Mikkola & Aarseth 1993, Mikkola &
+/// Merritt 2006) for close triple encounters.
+///
+/// The bodies are ordered into a chain so that the two shorter sides of the
+/// triangle are integrated directly as relative vectors, which keeps the
+/// separation of a close pair accurate when it is tiny compared to the
+/// system. The time transformation `dt/ds = 1 / (U + Omega)` of the
+/// logarithmic Hamiltonian leapfrog makes the leapfrog regular through
+/// collisions and exact for the Kepler orbit of an isolated pair, the TTL term
+/// `Omega` taking over for pairs with negligible mass that contribute nothing
+/// to `U`. The leapfrog is then extrapolated Bulirsch–Stoer style to high
+/// order, with the step size and the number of columns chosen from the
+/// estimated error and work.
+pub struct ChainKernel;
+
+/// Chain vectors with velocities, physical time and the TTL variable `W`,
+/// which tracks `Omega` along the solution.
+#[derive(Clone, Copy)]
+struct Chain {
+    x: [Vec3; 2],
+    w: [Vec3; 2],
+    t: f64,
+    ttl: f64,
+}
+
+impl Chain {
+    /// `self + (self - other) * c`, the extrapolation update.
+    #[inline(always)]
+    fn extrapolate(&self, other: &Chain, c: f64) -> Chain {
+        let cv = Vec3::splat(c);
+        let mut x = self.x;
+        let mut w = self.w;
+        for k in 0..2 {
+            x[k] = Vec3::mul_add(x[k] - other.x[k], cv, x[k]);
+            w[k] = Vec3::mul_add(w[k] - other.w[k], cv, w[k]);
+        }
+        Chain {
+            x: x,
+            w: w,
+            t: self.t + (self.t - other.t) * c,
+            ttl: self.ttl + (self.ttl - other.ttl) * c,
+        }
+    }
+
+    /// Positions or velocities relative to the first body of the chain.
+    #[inline(always)]
+    fn bodies(x: &[Vec3; 2]) -> [Vec3; 3] {
+        [Vec3::ZERO, x[0], x[0] + x[1]]
+    }
+
+    #[inline(always)]
+    fn separations(x: &[Vec3; 2]) -> [Vec3; 3] {
+        [x[0], x[1], x[0] + x[1]]
+    }
+}
+
+/// Masses and constants in chain order.
+struct System {
+    order: [usize; 3],
+    m: [f64; 3],
+    mu: [f64; 3],
+    omega: [f64; 3],
+    total_mass: f64,
+    binding_energy: f64,
+    softening: Softening,
+}
+
+impl System {
+    fn new(order: [usize; 3], m: &[f64; 3], softening: Softening) -> System {
+        let m = [m[order[0]], m[order[1]], m[order[2]]];
+        let total_mass = m[0] + m[1] + m[2];
+        let mean = total_mass / 3.0;
+        let mut omega = [0.0; 3];
+        for (k, &(a, b)) in PAIRS.iter().enumerate() {
+            if m[a] * m[b] < SMALL_MASS * mean * mean {
+                omega[k] = util::GRAVITY_CONSTANT * mean * mean;
+            }
+        }
+        System {
+            order: order,
+            m: m,
+            mu: m.map(|m| m * util::GRAVITY_CONSTANT),
+            omega: omega,
+            total_mass: total_mass,
+            binding_energy: 0.0,
+            softening: softening,
+        }
+    }
+
+    /// Kinetic energy in the center of mass frame.
+    fn kinetic_energy(&self, w: &[Vec3; 2]) -> f64 {
+        let u = Chain::bodies(w);
+        let u_com = (self.m[0] * u[0] + self.m[1] * u[1] + self.m[2] * u[2]) / self.total_mass;
+        let mut e_k = 0.0;
+        for c in 0..3 {
+            e_k += 0.5 * self.m[c] * (u[c] - u_com).norm_squared();
+        }
+        return e_k;
+    }
+
+    /// Potential energy `U` (positive) and the TTL function `Omega`.
+    fn potential(&self, x: &[Vec3; 2]) -> (f64, f64) {
+        let r = Chain::separations(x);
+        let mut u = 0.0;
+        let mut omega = 0.0;
+        for (k, &(a, b)) in PAIRS.iter().enumerate() {
+            let r2 = r[k].norm_squared();
+            u += self.mu[a] * self.m[b] * self.softening.potential(r2);
+            omega += self.omega[k] / r2.sqrt();
+        }
+        return (u, omega);
+    }
+
+    #[inline(always)]
+    fn drift(&self, c: &mut Chain, h: f64) {
+        let dt = h / (self.kinetic_energy(&c.w) + self.binding_energy + c.ttl);
+        let dtv = Vec3::splat(dt);
+        c.x[0] = Vec3::mul_add(c.w[0], dtv, c.x[0]);
+        c.x[1] = Vec3::mul_add(c.w[1], dtv, c.x[1]);
+        c.t += dt;
+    }
+
+    #[inline(always)]
+    fn kick(&self, c: &mut Chain, h: f64) {
+        let r = Chain::separations(&c.x);
+        let mut u = 0.0;
+        let mut omega = 0.0;
+        let mut a = [Vec3::ZERO; 3];
+        // Gradient of Omega with respect to the body positions.
+        let mut g = [Vec3::ZERO; 3];
+        for (k, &(i, j)) in PAIRS.iter().enumerate() {
+            let r2 = r[k].norm_squared();
+            let inv = 1.0 / r2.sqrt();
+            // Omega is not softened, it only sets the time transformation.
+            let f = r[k] * (inv * inv * inv);
+            let fs = r[k] * self.softening.force(r2);
+            u += self.mu[i] * self.m[j] * self.softening.potential(r2);
+            omega += self.omega[k] * inv;
+            a[i] = Vec3::mul_add(fs, Vec3::splat(self.mu[j]), a[i]);
+            a[j] = Vec3::mul_neg_add(fs, Vec3::splat(self.mu[i]), a[j]);
+            g[i] = Vec3::mul_add(f, Vec3::splat(self.omega[k]), g[i]);
+            g[j] = Vec3::mul_neg_add(f, Vec3::splat(self.omega[k]), g[j]);
+        }
+        let dt = h / (u + omega);
+        let dtv = Vec3::splat(dt);
+        let u0 = Chain::bodies(&c.w);
+        c.w[0] = Vec3::mul_add(a[1] - a[0], dtv, c.w[0]);
+        c.w[1] = Vec3::mul_add(a[2] - a[1], dtv, c.w[1]);
+        let u1 = Chain::bodies(&c.w);
+        // dW/dt = grad Omega . v, with the velocity averaged over the kick to
+        // keep the map symmetric. The gradients sum to zero, so velocities
+        // relative to the first body do.
+        let mut dw = 0.0;
+        for i in 0..3 {
+            dw += (g[i] * (u0[i] + u1[i])).reduce_add() / 2.0;
+        }
+        c.ttl += dt * dw;
+    }
+
+    /// Leapfrog `D(h/2) K(h) D(h/2)` over `h` in `n` substeps.
+    fn leapfrog(&self, c: &Chain, h: f64, n: usize) -> Chain {
+        let hs = h / n as f64;
+        let mut c = *c;
+        self.drift(&mut c, hs / 2.0);
+        for i in 0..n {
+            self.kick(&mut c, hs);
+            self.drift(&mut c, if i + 1 == n { hs / 2.0 } else { hs });
+        }
+        return c;
+    }
+
+    /// Reorders the chain so that the longest side of the triangle is the one
+    /// not integrated directly.
+    fn rechain(&mut self, c: &mut Chain, m: &[f64; 3]) {
+        let r = Chain::separations(&c.x).map(|r| r.norm());
+        let (first, middle, last) = if r[0] > r[1] && r[0] > r[2] {
+            (0, 2, 1)
+        } else if r[1] > r[2] {
+            (1, 0, 2)
+        } else {
+            return;
+        };
+        let q = Chain::bodies(&c.x);
+        let u = Chain::bodies(&c.w);
+        c.x = [q[middle] - q[first], q[last] - q[middle]];
+        c.w = [u[middle] - u[first], u[last] - u[middle]];
+        let binding_energy = self.binding_energy;
+        let order = self.order;
+        *self = System::new(
+            [order[first], order[middle], order[last]],
+            m,
+            self.softening,
+        );
+        self.binding_energy = binding_energy;
+    }
+
+    /// Weighted RMS norm of the difference between the last two columns
+    /// `c1` and `c1_low`, `<= 1` means accept.
+    fn error_norm(c0: &Chain, c1: &Chain, c1_low: &Chain, tolerance: &Tolerance) -> f64 {
+        let mut sum = 0.0;
+        let mut count = 0.0;
+        let mut add = |e: f64, scale: f64| {
+            if scale > 0.0 {
+                sum += (e / scale).powi(2);
+                count += 1.0;
+            }
+        };
+        for k in 0..2 {
+            let sc_x = tolerance.atol + tolerance.rtol * c0.x[k].norm().max(c1.x[k].norm());
+            let sc_w = tolerance.atol + tolerance.rtol * c0.w[k].norm().max(c1.w[k].norm());
+            add((c1.x[k] - c1_low.x[k]).norm(), sc_x);
+            add((c1.w[k] - c1_low.w[k]).norm(), sc_w);
+        }
+        add(c1.t - c1_low.t, tolerance.rtol * (c1.t - c0.t).abs());
+        add(
+            c1.ttl - c1_low.ttl,
+            tolerance.rtol * c0.ttl.abs().max(c1.ttl.abs()),
+        );
+        return (sum / count).sqrt();
+    }
+
+    /// One extrapolated step of length `h` in `s`, accepted as soon as one of
+    /// the columns `k - 1` to `k + 1` meets the tolerance.
+    fn step(&self, c: &Chain, h: f64, k: usize, tolerance: &Tolerance) -> Step {
+        let mut h_opt = [0.0; MAX_COLUMNS];
+        let mut prev = [*c; MAX_COLUMNS];
+        for j in 0..=(k + 1) {
+            let n = 2 * (j + 1);
+            let mut row = [*c; MAX_COLUMNS];
+            row[0] = self.leapfrog(c, h, n);
+            for l in 1..=j {
+                let ratio = (n as f64 / (2 * (j - l + 1)) as f64).powi(2);
+                row[l] = row[l - 1].extrapolate(&prev[l - 1], 1.0 / (ratio - 1.0));
+            }
+            if j >= 1 {
+                let err = Self::error_norm(c, &row[j], &row[j - 1], tolerance);
+                let factor = if err == 0.0 {
+                    MAX_FACTOR
+                } else {
+                    (SAFETY * (0.65 / err).powf(1.0 / (2 * j + 1) as f64))
+                        .clamp(MIN_FACTOR, MAX_FACTOR)
+                };
+                h_opt[j] = h * factor;
+                if j + 1 >= k && (err <= 1.0 || j == k + 1) {
+                    return Step {
+                        c: row[j],
+                        column: j,
+                        accepted: err <= 1.0,
+                        h_opt: h_opt,
+                    };
+                }
+            }
+            prev = row;
+        }
+        unreachable!();
+    }
+}
+
+/// Result of `System::step`: the most extrapolated column computed, which
+/// is the new chain if `accepted`, and the optimal step size for each column.
+struct Step {
+    c: Chain,
+    column: usize,
+    accepted: bool,
+    h_opt: [f64; MAX_COLUMNS],
+}
+
+impl ChainKernel {
+    fn integrate(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+        mut dense: Option<&mut DenseOutput>,
+    ) -> (ThreeBodyState, StepStats) {
+        state.assert_newtonian_only("ChainKernel", &[]);
+        let t0 = state.time();
+        let t_end = duration as f64 * util::UNIT_TIME;
+        // Kicks per step up to each column.
+        let cost: [f64; MAX_COLUMNS] = std::array::from_fn(|j| ((j + 1) * (j + 2)) as f64);
+
+        let mut system = System::new([0, 1, 2], &state.m, state.softening);
+        let mut c = Chain {
+            x: [state.p[1] - state.p[0], state.p[2] - state.p[1]],
+            w: [state.v[1] - state.v[0], state.v[2] - state.v[1]],
+            t: 0.0,
+            ttl: 0.0,
+        };
+        system.rechain(&mut c, &state.m);
+        let (u, omega) = system.potential(&c.x);
+        system.binding_energy = u - system.kinetic_energy(&c.w);
+        c.ttl = omega;
+
+        let mut h = 0.01 * dynamical_time(&state.p, &state.m).min(t_end) * (u + omega);
+        assert!(
+            h > 0.0 || t_end == 0.0,
+            "ChainKernel needs a body with mass to set the step size"
+        );
+        // The leapfrog follows Kepler orbits exactly and takes steps of a good
+        // fraction of an orbit, far too long for a cubic interpolation, whose
+        // relative error over `theta` radians of the orbit is about
+        // `theta^4 / 384`. Dense output segments are kept to the `theta`
+        // meeting the tolerance, `dynamical_time * (U + Omega)` being the
+        // fictitious time per radian of a circular orbit.
+        let theta = (384.0 * tolerance.rtol).powf(0.25).clamp(1e-3, 0.1);
+        let h_dense = theta * dynamical_time(&state.p, &state.m).min(t_end) * (u + omega);
+        let mut k = 4;
+        let mut last_rejected = false;
+        let mut stats = StepStats::default();
+
+        // Back to absolute coordinates, with the center of mass moving freely.
+        let mut com = Vec3::ZERO;
+        let mut v_com = Vec3::ZERO;
+        for i in 0..3 {
+            com += state.m[i] * state.p[i];
+            v_com += state.m[i] * state.v[i];
+        }
+        let com = com / system.total_mass;
+        let v_com = v_com / system.total_mass;
+        let absolute = |system: &System, c: &Chain| {
+            let q = Chain::bodies(&c.x);
+            let u = Chain::bodies(&c.w);
+            let mut q_com = Vec3::ZERO;
+            let mut u_com = Vec3::ZERO;
+            for i in 0..3 {
+                q_com += system.m[i] * q[i];
+                u_com += system.m[i] * u[i];
+            }
+            let q_com = q_com / system.total_mass;
+            let u_com = u_com / system.total_mass;
+            let com = Vec3::mul_add(v_com, Vec3::splat(c.t), com);
+            let mut y = Phase::ZERO;
+            for i in 0..3 {
+                y.p[system.order[i]] = com + (q[i] - q_com);
+                y.v[system.order[i]] = v_com + (u[i] - u_com);
+            }
+            return y;
+        };
+        // The chain integrates softened Newtonian gravity alone.
+        let m = state.m.map(|m| Vec3::splat(m * util::GRAVITY_CONSTANT));
+        let derivative = |y: &Phase| Phase {
+            p: y.v,
+            v: calc_a(&y.p, &m, &state.softening),
+        };
+
+        while c.t < t_end {
+            // The step in physical time, as h is in the fictitious time.
+            let (u, omega) = system.potential(&c.x);
+            check_step("ChainKernel", h / (u + omega), c.t, t0);
+            let step = system.step(&c, h, k, &tolerance);
+            let h_opt = step.h_opt;
+            if !step.accepted {
+                stats.rejected += 1;
+                h = h_opt[k].min(h * 0.5);
+                last_rejected = true;
+                continue;
+            }
+            let j = step.column;
+            // Shorter steps from the same start are at least as accurate as the
+            // accepted one, but are checked all the same.
+            let sub_step = |hs: f64| {
+                let step = system.step(&c, hs, j.clamp(2, MAX_COLUMNS - 2), &tolerance);
+                return if step.accepted { Some(step.c) } else { None };
+            };
+            let mut c1 = Some(step.c);
+            let mut h_step = h;
+
+            if step.c.t > t_end {
+                // Overshot, so solve t(h) = t_end with Newton's method, using
+                // dt/ds = 1 / (U + Omega) at the end point.
+                let mut hs = h * (t_end - c.t) / (step.c.t - c.t);
+                for _ in 0..MAX_ITERATIONS {
+                    c1 = sub_step(hs);
+                    let Some(c1) = &c1 else {
+                        break;
+                    };
+                    h_step = hs;
+                    let (u, omega) = system.potential(&c1.x);
+                    let dh = (t_end - c1.t) * (u + omega);
+                    hs += dh;
+                    if dh.abs() <= 1e-15 * hs.abs() {
+                        break;
+                    }
+                }
+                if let Some(c1) = &mut c1 {
+                    c1.t = t_end;
+                }
+            }
+            // Intermediate points of the dense output, by shorter steps again.
+            let n = match dense {
+                Some(_) => (h_step / h_dense).ceil().max(1.0) as usize,
+                None => 1,
+            };
+            let mut points: Vec<Option<Chain>> = (1..n)
+                .map(|i| sub_step(h_step * i as f64 / n as f64))
+                .collect();
+            points.push(c1);
+            let Some(points) = points.into_iter().collect::<Option<Vec<Chain>>>() else {
+                // A shorter step failed where the full one passed, as can
+                // happen with a tolerance near roundoff, so retry shorter.
+                stats.rejected += 1;
+                h = h_step.min(h * 0.5);
+                last_rejected = true;
+                continue;
+            };
+            let c1 = points[n - 1];
+            stats.accepted += 1;
+            if let Some(dense) = &mut dense {
+                let mut c0 = c;
+                let mut y0 = absolute(&system, &c0);
+                let mut f0 = derivative(&y0);
+                for c_i in &points {
+                    let y1 = absolute(&system, c_i);
+                    let f1 = derivative(&y1);
+                    dense.push_hermite(t0 + c0.t, c_i.t - c0.t, &y0, &f0, &y1, &f1);
+                    c0 = *c_i;
+                    y0 = y1;
+                    f0 = f1;
+                }
+            }
+            c = c1;
+            system.rechain(&mut c, &state.m);
+
+            // Pick the next column by the work per unit step.
+            let work = |j: usize| cost[j] / h_opt[j];
+            let (k_new, h_new) = if j > 1 && work(j - 1) < 0.8 * work(j) {
+                (j - 1, h_opt[j - 1])
+            } else if j > 1 && j + 1 < MAX_COLUMNS && work(j) < 0.9 * work(j - 1) {
+                (j + 1, h_opt[j] * cost[j + 1] / cost[j])
+            } else {
+                (j, h_opt[j])
+            };
+            k = k_new.clamp(2, MAX_COLUMNS - 2);
+            h = if last_rejected { h_new.min(h) } else { h_new };
+            last_rejected = false;
+        }
+
+        let y = absolute(&system, &c);
+
+        let state = ThreeBodyState {
+            p: y.p,
+            v: y.v,
+            m: state.m,
+            t: state.t + duration,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        };
+        return (state, stats);
+    }
+}
+
+impl AdaptiveThreeBodyKernel for ChainKernel {
+    fn kernel_adaptive(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+    ) -> (ThreeBodyState, StepStats) {
+        Self::integrate(state, duration, tolerance, None)
+    }
+
+    /// Interpolates by cubic Hermite polynomials in physical time, splitting
+    /// the steps into segments short enough to meet the tolerance, which
+    /// costs an extra extrapolated step per segment.
+    fn kernel_adaptive_dense(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+    ) -> (ThreeBodyState, StepStats, DenseOutput) {
+        let mut dense = DenseOutput::default();
+        let (state, stats) = Self::integrate(state, duration, tolerance, Some(&mut dense));
+        return (state, stats, dense);
+    }
+}
+
+impl ThreeBodyKernel for ChainKernel {
+    fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
+        Self::kernel_adaptive(state, steps * dt, TOLERANCE).0
+    }
+
+    fn kernel_dense(state: ThreeBodyState, steps: u64, dt: u64) -> (ThreeBodyState, DenseOutput) {
+        let (state, _, dense) = Self::kernel_adaptive_dense(state, steps * dt, TOLERANCE);
+        return (state, dense);
+    }
+}
@@ -0,0 +1,171 @@
+use super::*;
+
+/// The symmetric composition `S(w_s h) ... S(w_2 h) S(w_1 h)` of the leapfrog
+/// `S(h) = D(h/2) K(h) D(h/2)`, which raises its order from 2 to that of the
+/// weights, as a drift–kick splitting. The half drifts of neighbouring stages
+/// are merged, as in `Yoshida4Kernel`. `T` must be `S + 1`.
+const fn composition<const S: usize, const T: usize>(w: [f64; S]) -> ([f64; T], [f64; S]) {
+    assert!(T == S + 1);
+    let mut c = [0.0; T];
+    c[0] = w[0] / 2.0;
+    let mut i = 1;
+    while i < S {
+        c[i] = (w[i - 1] + w[i]) / 2.0;
+        i += 1;
+    }
+    c[S] = w[S - 1] / 2.0;
+    return (c, w);
+}
+
+/// Expands the weights from the outermost one to the central one into the
+/// full symmetric sequence. `S` must be `2 H - 1`.
+const fn mirror<const H: usize, const S: usize>(half: [f64; H]) -> [f64; S] {
+    assert!(S == 2 * H - 1);
+    let mut w = [0.0; S];
+    let mut i = 0;
+    while i < H {
+        w[i] = half[i];
+        w[S - 1 - i] = half[i];
+        i += 1;
+    }
+    return w;
+}
+
+// The weights below were refined with Newton's method on the order conditions
+// in 200 digit arithmetic, starting from the published values, and are
+// rounded to the nearest f64.
+
+/// Yoshida (1990), 6th order, solution A, 7 stages.
+pub struct Yoshida6;
+
+const YOSHIDA_6: ([f64; 8], [f64; 7]) = composition(mirror::<4, 7>([
+    0.7845136104775573,
+    0.23557321335935813,
+    -1.177679984178871,
+    1.3151863206839112,
+]));
+
+impl Splitting for Yoshida6 {
+    const C: &'static [f64] = &YOSHIDA_6.0;
+    const D: &'static [f64] = &YOSHIDA_6.1;
+}
+
+/// Yoshida (1990), 8th order, solution A, 15 stages. The published 15 digits
+/// satisfy the order conditions only to about 1e-11.
+pub struct Yoshida8;
+
+const YOSHIDA_8: ([f64; 16], [f64; 15]) = composition(mirror::<8, 15>([
+    1.0424262086997043,
+    1.82020630970698,
+    0.15773992812370832,
+    2.440027326166344,
+    -0.007169894197095332,
+    -2.446991823704246,
+    -1.6158237415006538,
+    -1.7808286265894835,
+]));
+
+impl Splitting for Yoshida8 {
+    const C: &'static [f64] = &YOSHIDA_8.0;
+    const D: &'static [f64] = &YOSHIDA_8.1;
+}
+
+/// Kahan & Li (1997), 6th order with 9 stages (s9odr6a), the spare degree of
+/// freedom chosen to minimize the leading error term.
+pub struct KahanLi6;
+
+const KAHAN_LI_6: ([f64; 10], [f64; 9]) = composition(mirror::<5, 9>([
+    0.3921614440073141,
+    0.33259913678935943,
+    -0.7062461725576393,
+    0.0822135962935508,
+    0.79854399093483,
+]));
+
+impl Splitting for KahanLi6 {
+    const C: &'static [f64] = &KAHAN_LI_6.0;
+    const D: &'static [f64] = &KAHAN_LI_6.1;
+}
+
+/// Kahan & Li (1997), 8th order with 17 stages (s17odr8a).
+pub struct KahanLi8;
+
+const KAHAN_LI_8: ([f64; 18], [f64; 17]) = composition(mirror::<9, 17>([
+    0.13020248308889007,
+    0.5611629817751084,
+    -0.38947496264484727,
+    0.1588419065551556,
+    -0.39590389413323757,
+    0.1845396409783157,
+    0.25837438768632204,
+    0.2950117236093103,
+    -0.6055085338300346,
+]));
+
+impl Splitting for KahanLi8 {
+    const C: &'static [f64] = &KAHAN_LI_8.0;
+    const D: &'static [f64] = &KAHAN_LI_8.1;
+}
+
+/// Suzuki's (1990) fractal composition
+/// `S_2k(h) = S_2k-2(p h)^2 S_2k-2((1 - 4p) h) S_2k-2(p h)^2`, with
+/// `p = 1 / (4 - 4^(1 / (2k - 1)))` given for each level from order 4 up.
+/// `S` must be `5^K`.
+const fn suzuki<const K: usize, const S: usize>(p: [f64; K]) -> [f64; S] {
+    let mut w = [1.0; S];
+    let mut stride = S;
+    let mut k = K;
+    while k > 0 {
+        k -= 1;
+        stride /= 5;
+        let mut i = 0;
+        while i < S {
+            // Digit of the stage index at this level picks the substep.
+            if (i / stride) % 5 == 2 {
+                w[i] *= 1.0 - 4.0 * p[k];
+            } else {
+                w[i] *= p[k];
+            }
+            i += 1;
+        }
+    }
+    assert!(stride == 1);
+    return w;
+}
+
+const SUZUKI_P4: f64 = 0.4144907717943757;
+const SUZUKI_P6: f64 = 0.37306582773327285;
+const SUZUKI_P8: f64 = 0.35958464934999224;
+
+/// Suzuki's fractal composition, 6th order, 25 stages.
+pub struct Suzuki6;
+
+const SUZUKI_6: ([f64; 26], [f64; 25]) = composition(suzuki([SUZUKI_P4, SUZUKI_P6]));
+
+impl Splitting for Suzuki6 {
+    const C: &'static [f64] = &SUZUKI_6.0;
+    const D: &'static [f64] = &SUZUKI_6.1;
+}
+
+/// Suzuki's fractal composition, 8th order, 125 stages.
+pub struct Suzuki8;
+
+const SUZUKI_8: ([f64; 126], [f64; 125]) = composition(suzuki([SUZUKI_P4, SUZUKI_P6, SUZUKI_P8]));
+
+impl Splitting for Suzuki8 {
+    const C: &'static [f64] = &SUZUKI_8.0;
+    const D: &'static [f64] = &SUZUKI_8.1;
+}
+
+pub type Yoshida6Kernel = SplittingKernel<Yoshida6>;
+pub type Yoshida6RelativeKernel = SplittingRelativeKernel<Yoshida6>;
+pub type Yoshida8Kernel = SplittingKernel<Yoshida8>;
+pub type Yoshida8RelativeKernel = SplittingRelativeKernel<Yoshida8>;
+pub type KahanLi6Kernel = SplittingKernel<KahanLi6>;
+pub type KahanLi6RelativeKernel = SplittingRelativeKernel<KahanLi6>;
+pub type KahanLi8Kernel = SplittingKernel<KahanLi8>;
+pub type KahanLi8RelativeKernel = SplittingRelativeKernel<KahanLi8>;
+pub type Suzuki6Kernel = SplittingKernel<Suzuki6>;
+pub type Suzuki6RelativeKernel = SplittingRelativeKernel<Suzuki6>;
+pub type Suzuki8Kernel = SplittingKernel<Suzuki8>;
+pub type Suzuki8RelativeKernel = SplittingRelativeKernel<Suzuki8>;
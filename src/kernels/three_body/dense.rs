@@ -0,0 +1,139 @@
+use crate::Vec3;
+
+use super::*;
+
+/// One step of a dense output, with the positions and velocities as
+/// polynomials `sum_k c[k] s^k` in the fraction `s` of the step.
+#[derive(Clone, Debug)]
+struct Segment {
+    t0: f64,
+    h: f64,
+    c: Vec<Phase>,
+}
+
+/// Positions and velocities at any time inside an integrated interval, as
+/// recorded by `kernel_dense` and `kernel_adaptive_dense`.
+///
+/// Each step is interpolated on its own, by the continuous extension of the
+/// method where it has one, and otherwise by cubic Hermite polynomials in the
+/// positions and the velocities from their values and derivatives at both
+/// ends, which are continuous across steps and have an error of order `h^4`.
+#[derive(Clone, Debug, Default)]
+pub struct DenseOutput {
+    segments: Vec<Segment>,
+}
+
+impl DenseOutput {
+    /// Start of the interval, in seconds.
+    pub fn t_start(&self) -> f64 {
+        return self.segments.first().map_or(0.0, |s| s.t0);
+    }
+
+    /// End of the interval, in seconds.
+    pub fn t_end(&self) -> f64 {
+        return self.segments.last().map_or(0.0, |s| s.t0 + s.h);
+    }
+
+    /// Number of steps.
+    pub fn len(&self) -> usize {
+        return self.segments.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.segments.is_empty();
+    }
+
+    /// Positions and velocities at the time `t` in seconds, or `None` if
+    /// there are no steps. Outside the interval the first or last step is
+    /// extrapolated.
+    pub fn evaluate(&self, t: f64) -> Option<([Vec3; 3], [Vec3; 3])> {
+        if self.segments.is_empty() {
+            return None;
+        }
+        return Some(self.interpolate(t));
+    }
+
+    /// `evaluate` with at least one step.
+    fn interpolate(&self, t: f64) -> ([Vec3; 3], [Vec3; 3]) {
+        let k = self
+            .segments
+            .partition_point(|s| s.t0 + s.h < t)
+            .min(self.segments.len() - 1);
+        let segment = &self.segments[k];
+        let s = (t - segment.t0) / segment.h;
+        let mut y = Phase::ZERO;
+        for c in segment.c.iter().rev() {
+            y = c.advance(&y, s);
+        }
+        return (y.p, y.v);
+    }
+
+    /// Events of `events` within the interval in order of time, up to the
+    /// first terminal one. Each event function is checked for a sign change
+    /// over each step, and its zero located on the interpolant.
+    pub fn find_events(&self, events: &[Arc<dyn EventFunction>]) -> Vec<Event> {
+        let mut found = vec![];
+        if self.segments.is_empty() {
+            return found;
+        }
+        let value = |k: usize, t: f64| {
+            let (p, v) = self.interpolate(t);
+            return events[k].value(&p, &v);
+        };
+        let mut g0: Vec<f64> = (0..events.len())
+            .map(|k| value(k, self.t_start()))
+            .collect();
+        for segment in &self.segments {
+            let t0 = segment.t0;
+            let t1 = segment.t0 + segment.h;
+            let mut hits = vec![];
+            for k in 0..events.len() {
+                let g1 = value(k, t1);
+                if events[k].direction().crosses(g0[k], g1) {
+                    hits.push((find_root(|t| value(k, t), t0, t1, g0[k], g1), k));
+                }
+                g0[k] = g1;
+            }
+            hits.sort_by(|x, y| x.0.total_cmp(&y.0));
+            for (t, k) in hits {
+                let (p, v) = self.interpolate(t);
+                found.push(Event {
+                    event: k,
+                    t: t,
+                    p: p.to_vec(),
+                    v: v.to_vec(),
+                });
+                if events[k].terminal() {
+                    return found;
+                }
+            }
+        }
+        return found;
+    }
+
+    /// Appends a step of length `h` starting at `t0` from its polynomial
+    /// coefficients.
+    pub(super) fn push(&mut self, t0: f64, h: f64, c: Vec<Phase>) {
+        self.segments.push(Segment { t0: t0, h: h, c: c });
+    }
+
+    /// Appends a step of length `h` starting at `t0` by cubic Hermite
+    /// interpolation from `y0` and `y1` and their derivatives `f0` and `f1`.
+    pub(super) fn push_hermite(
+        &mut self,
+        t0: f64,
+        h: f64,
+        y0: &Phase,
+        f0: &Phase,
+        y1: &Phase,
+        f1: &Phase,
+    ) {
+        let dy = y1.advance(y0, -1.0);
+        let c2 = Phase::ZERO
+            .advance(&dy, 3.0)
+            .advance(f0, -2.0 * h)
+            .advance(f1, -h);
+        let c3 = Phase::ZERO.advance(&dy, -2.0).advance(f0, h).advance(f1, h);
+        self.push(t0, h, vec![*y0, Phase::ZERO.advance(f0, h), c2, c3]);
+    }
+}
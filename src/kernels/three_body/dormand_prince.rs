@@ -0,0 +1,236 @@
+use crate::util;
+
+use super::*;
+
+const A21: f64 = 1.0 / 5.0;
+const A31: f64 = 3.0 / 40.0;
+const A32: f64 = 9.0 / 40.0;
+const A41: f64 = 44.0 / 45.0;
+const A42: f64 = -56.0 / 15.0;
+const A43: f64 = 32.0 / 9.0;
+const A51: f64 = 19372.0 / 6561.0;
+const A52: f64 = -25360.0 / 2187.0;
+const A53: f64 = 64448.0 / 6561.0;
+const A54: f64 = -212.0 / 729.0;
+const A61: f64 = 9017.0 / 3168.0;
+const A62: f64 = -355.0 / 33.0;
+const A63: f64 = 46732.0 / 5247.0;
+const A64: f64 = 49.0 / 176.0;
+const A65: f64 = -5103.0 / 18656.0;
+// The 5th order solution, which is also the last stage (FSAL).
+const A71: f64 = 35.0 / 384.0;
+const A73: f64 = 500.0 / 1113.0;
+const A74: f64 = 125.0 / 192.0;
+const A75: f64 = -2187.0 / 6784.0;
+const A76: f64 = 11.0 / 84.0;
+
+// Difference between the 5th and the embedded 4th order weights.
+const E1: f64 = 71.0 / 57600.0;
+const E3: f64 = -71.0 / 16695.0;
+const E4: f64 = 71.0 / 1920.0;
+const E5: f64 = -17253.0 / 339200.0;
+const E6: f64 = 22.0 / 525.0;
+const E7: f64 = -1.0 / 40.0;
+
+// Weights of the stages in the 4th order continuous extension (Hairer,
+// Nørsett & Wanner, II.6), the term that is not fixed by the ends of the step.
+const DENSE: [f64; 7] = [
+    -12715105075.0 / 11282082432.0,
+    0.0,
+    87487479700.0 / 32700410799.0,
+    -10690763975.0 / 1880347072.0,
+    701980252875.0 / 199316789632.0,
+    -1453857185.0 / 822651844.0,
+    69997945.0 / 29380423.0,
+];
+
+const SAFETY: f64 = 0.9;
+const MIN_FACTOR: f64 = 0.2;
+const MAX_FACTOR: f64 = 5.0;
+
+/// Dormand–Prince 5(4) with local extrapolation and a standard
+/// `err^(-1/5)` step size controller.
+pub struct DormandPrince54Kernel;
+
+impl DormandPrince54Kernel {
+    /// Initial step guess from Hairer, Nørsett & Wanner, II.4.
+    fn initial_step(y0: &Phase, f0: &Phase, forces: &Forces, tolerance: &Tolerance) -> f64 {
+        let d0 = error_norm(y0, y0, y0, tolerance);
+        let d1 = error_norm(y0, y0, f0, tolerance);
+        let h0 = if d0 < 1e-5 || d1 < 1e-5 {
+            1e-6
+        } else {
+            0.01 * d0 / d1
+        };
+        let y1 = y0.advance(f0, h0);
+        let f1 = y1.derivative(forces);
+        let df = f1.advance(f0, -1.0);
+        let d2 = error_norm(y0, y0, &df, tolerance) / h0;
+        let h1 = if d1.max(d2) <= 1e-15 {
+            (h0 * 1e-3).max(1e-6)
+        } else {
+            (0.01 / d1.max(d2)).powf(1.0 / 5.0)
+        };
+        return (100.0 * h0).min(h1);
+    }
+
+    fn integrate(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+        mut dense: Option<&mut DenseOutput>,
+    ) -> (ThreeBodyState, StepStats) {
+        let forces = Forces::new(&state);
+        let t0 = state.time();
+        let t_end = duration as f64 * util::UNIT_TIME;
+
+        let mut stats = StepStats::default();
+        let mut y = Phase {
+            p: state.p,
+            v: state.v,
+        };
+        let mut k1 = y.derivative(&forces);
+        let mut t = 0.0;
+        let mut h = Self::initial_step(&y, &k1, &forces, &tolerance);
+        let mut last_rejected = false;
+
+        while t < t_end {
+            check_step("DormandPrince54Kernel", h, t, t0);
+            let last = t + h >= t_end;
+            if last {
+                h = t_end - t;
+            }
+
+            let y2 = y.advance(&k1, h * A21);
+            let k2 = y2.derivative(&forces);
+            let y3 = y.advance(&k1, h * A31).advance(&k2, h * A32);
+            let k3 = y3.derivative(&forces);
+            let y4 = y
+                .advance(&k1, h * A41)
+                .advance(&k2, h * A42)
+                .advance(&k3, h * A43);
+            let k4 = y4.derivative(&forces);
+            let y5 = y
+                .advance(&k1, h * A51)
+                .advance(&k2, h * A52)
+                .advance(&k3, h * A53)
+                .advance(&k4, h * A54);
+            let k5 = y5.derivative(&forces);
+            let y6 = y
+                .advance(&k1, h * A61)
+                .advance(&k2, h * A62)
+                .advance(&k3, h * A63)
+                .advance(&k4, h * A64)
+                .advance(&k5, h * A65);
+            let k6 = y6.derivative(&forces);
+            let y7 = y
+                .advance(&k1, h * A71)
+                .advance(&k3, h * A73)
+                .advance(&k4, h * A74)
+                .advance(&k5, h * A75)
+                .advance(&k6, h * A76);
+            let k7 = y7.derivative(&forces);
+
+            let err = Phase::ZERO
+                .advance(&k1, h * E1)
+                .advance(&k3, h * E3)
+                .advance(&k4, h * E4)
+                .advance(&k5, h * E5)
+                .advance(&k6, h * E6)
+                .advance(&k7, h * E7);
+            let err = error_norm(&y, &y7, &err, &tolerance);
+
+            if err <= 1.0 {
+                stats.accepted += 1;
+                if let Some(dense) = &mut dense {
+                    // y(s) = y + s (r2 + (1 - s) (r3 + s (r4 + (1 - s) r5))),
+                    // expanded in powers of s.
+                    let r2 = y7.advance(&y, -1.0);
+                    let r3 = Phase::ZERO.advance(&k1, h).advance(&r2, -1.0);
+                    let r4 = r2.advance(&k7, -h).advance(&r3, -1.0);
+                    let r5 = Phase::ZERO
+                        .advance(&k1, h * DENSE[0])
+                        .advance(&k3, h * DENSE[2])
+                        .advance(&k4, h * DENSE[3])
+                        .advance(&k5, h * DENSE[4])
+                        .advance(&k6, h * DENSE[5])
+                        .advance(&k7, h * DENSE[6]);
+                    let c = vec![
+                        y,
+                        r2.advance(&r3, 1.0),
+                        r4.advance(&r5, 1.0).advance(&r3, -1.0),
+                        Phase::ZERO.advance(&r4, -1.0).advance(&r5, -2.0),
+                        r5,
+                    ];
+                    dense.push(t0 + t, h, c);
+                }
+                t = if last { t_end } else { t + h };
+                y = y7;
+                k1 = k7;
+                let factor = if err == 0.0 {
+                    MAX_FACTOR
+                } else {
+                    (SAFETY * err.powf(-1.0 / 5.0)).clamp(MIN_FACTOR, MAX_FACTOR)
+                };
+                h *= if last_rejected {
+                    factor.min(1.0)
+                } else {
+                    factor
+                };
+                last_rejected = false;
+            } else {
+                stats.rejected += 1;
+                h *= (SAFETY * err.powf(-1.0 / 5.0)).max(MIN_FACTOR);
+                last_rejected = true;
+            }
+        }
+
+        let state = ThreeBodyState {
+            p: y.p,
+            v: y.v,
+            m: state.m,
+            t: state.t + duration,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        };
+        return (state, stats);
+    }
+}
+
+impl AdaptiveThreeBodyKernel for DormandPrince54Kernel {
+    fn kernel_adaptive(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+    ) -> (ThreeBodyState, StepStats) {
+        Self::integrate(state, duration, tolerance, None)
+    }
+
+    /// Uses the 4th order continuous extension of Dormand–Prince, from the
+    /// stages of each step at no extra cost.
+    fn kernel_adaptive_dense(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+    ) -> (ThreeBodyState, StepStats, DenseOutput) {
+        let mut dense = DenseOutput::default();
+        let (state, stats) = Self::integrate(state, duration, tolerance, Some(&mut dense));
+        return (state, stats, dense);
+    }
+}
+
+impl ThreeBodyKernel for DormandPrince54Kernel {
+    fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
+        Self::kernel_adaptive(state, steps * dt, Tolerance::default()).0
+    }
+
+    fn kernel_dense(state: ThreeBodyState, steps: u64, dt: u64) -> (ThreeBodyState, DenseOutput) {
+        let (state, _, dense) =
+            Self::kernel_adaptive_dense(state, steps * dt, Tolerance::default());
+        return (state, dense);
+    }
+}
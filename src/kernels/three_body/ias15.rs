@@ -0,0 +1,342 @@
+use crate::util;
+use crate::Vec3;
+
+use super::*;
+
+// Gauss–Radau spacings on [0, 1].
+const H: [f64; 8] = [
+    0.0,
+    0.05626256053692215,
+    0.18024069173689236,
+    0.3526247171131696,
+    0.5471536263305554,
+    0.7342101772154105,
+    0.8853209468390958,
+    0.9775206135612875,
+];
+
+const SAFETY_FACTOR: f64 = 0.25;
+const MAX_ITERATIONS: usize = 12;
+const EPSILON: f64 = 1e-9;
+
+type Bodies = [Vec3; 3];
+
+/// IAS15, the 15th order implicit integrator with adaptive step size of Rein &
+/// Spiegel (2015).
+///
+/// Within a step of length `dt` the acceleration is approximated by
+/// `a(h) = a0 + b0 h + ... + b6 h^7` for `h` in `[0, 1]`, whose coefficients are
+/// found by predictor–corrector iteration on the Gauss–Radau substeps. The
+/// next step size is chosen so that `|b6| / |a|` stays around `epsilon`,
+/// which for the default of 1e-9 keeps the truncation error below machine
+/// precision. Requests for a smaller `epsilon` than roundoff allows are capped
+/// at the roundoff level. Positions and velocities are accumulated with
+/// compensated summation.
+pub struct Ias15Kernel;
+
+/// `C[k][j]` is the coefficient of `h^(j+1)` in `h (h - h_1) ... (h - h_k)`,
+/// which turns the divided differences `g` into the polynomial coefficients
+/// `b`.
+fn newton_to_monomial() -> [[f64; 7]; 7] {
+    let mut c = [[0.0; 7]; 7];
+    // poly[j] is the coefficient of h^(j+1).
+    let mut poly = [0.0; 8];
+    poly[0] = 1.0;
+    for k in 0..7 {
+        if k > 0 {
+            for j in (0..=k).rev() {
+                poly[j] = if j > 0 { poly[j - 1] } else { 0.0 } - H[k] * poly[j];
+            }
+        }
+        c[k][..7].copy_from_slice(&poly[..7]);
+    }
+    return c;
+}
+
+fn g_to_b(g: &[Bodies; 7], c: &[[f64; 7]; 7]) -> [Bodies; 7] {
+    let mut b = [[Vec3::ZERO; 3]; 7];
+    for j in 0..7 {
+        for k in j..7 {
+            b[j] = advance(&b[j], &g[k], &Vec3::splat(c[k][j]));
+        }
+    }
+    return b;
+}
+
+fn b_to_g(b: &[Bodies; 7], c: &[[f64; 7]; 7]) -> [Bodies; 7] {
+    let mut g = [[Vec3::ZERO; 3]; 7];
+    for j in (0..7).rev() {
+        g[j] = b[j];
+        for k in (j + 1)..7 {
+            g[j] = advance(&g[j], &g[k], &Vec3::splat(-c[k][j]));
+        }
+    }
+    return g;
+}
+
+/// Sum of the magnitudes of the weights of the 7th divided difference, i.e.
+/// how much roundoff in the accelerations is amplified in `b6`.
+fn divided_difference_gain() -> f64 {
+    let mut gain = 0.0;
+    for n in 0..8 {
+        let mut w = 1.0;
+        for k in 0..8 {
+            if k != n {
+                w *= H[n] - H[k];
+            }
+        }
+        gain += 1.0 / w.abs();
+    }
+    return gain;
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    let mut c = 1.0;
+    for i in 0..k {
+        c = c * (n - i) as f64 / (i + 1) as f64;
+    }
+    return c;
+}
+
+#[inline(always)]
+fn max_norm(a: &Bodies) -> f64 {
+    a[0].norm().max(a[1].norm()).max(a[2].norm())
+}
+
+impl Ias15Kernel {
+    /// Position at substep `h` from the current acceleration polynomial.
+    #[inline(always)]
+    fn predict_p(
+        p0: &Bodies,
+        v0: &Bodies,
+        a0: &Bodies,
+        b: &[Bodies; 7],
+        h: f64,
+        dt: f64,
+    ) -> Bodies {
+        let mut s = mul_same(a0, &Vec3::splat(0.5));
+        let mut hk = h;
+        for k in 0..7 {
+            s = advance(&s, &b[k], &Vec3::splat(hk / ((k + 2) * (k + 3)) as f64));
+            hk *= h;
+        }
+        let p = advance(p0, v0, &Vec3::splat(h * dt));
+        return advance(&p, &s, &Vec3::splat(h * h * dt * dt));
+    }
+
+    /// Velocity at substep `h`, for accelerations depending on it.
+    #[inline(always)]
+    fn predict_v(v0: &Bodies, a0: &Bodies, b: &[Bodies; 7], h: f64, dt: f64) -> Bodies {
+        let mut s = *a0;
+        let mut hk = h;
+        for k in 0..7 {
+            s = advance(&s, &b[k], &Vec3::splat(hk / (k + 2) as f64));
+            hk *= h;
+        }
+        return advance(v0, &s, &Vec3::splat(h * dt));
+    }
+
+    /// Relative roundoff in the accelerations, from the cancellation in
+    /// `p_j - p_i` when the bodies are far from the origin compared to their
+    /// separation. `epsilon` below `gain` times this cannot be resolved, and
+    /// chasing it would shrink the step without bound.
+    fn roundoff(p: &Bodies, a: &Bodies, m: &[f64; 3]) -> f64 {
+        let mut noise: f64 = 0.0;
+        for i in 0..3 {
+            let mut amplification: f64 = 1.0;
+            for j in 0..3 {
+                if j != i && m[j] > 0.0 {
+                    let r = (p[i] - p[j]).norm();
+                    amplification = amplification.max((p[i].norm() + p[j].norm()) / r);
+                }
+            }
+            noise = noise.max(a[i].norm() * amplification);
+        }
+        return f64::EPSILON * noise / max_norm(a);
+    }
+
+    fn integrate(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+        mut dense: Option<&mut DenseOutput>,
+    ) -> (ThreeBodyState, StepStats) {
+        let forces = Forces::new(&state);
+        let t0 = state.time();
+        let epsilon = tolerance.rtol;
+        let c = newton_to_monomial();
+        let gain = divided_difference_gain();
+        let t_end = duration as f64 * util::UNIT_TIME;
+
+        let mut stats = StepStats::default();
+        let mut p0 = state.p;
+        let mut v0 = state.v;
+        let mut cp = [Vec3::ZERO; 3];
+        let mut cv = [Vec3::ZERO; 3];
+        let mut b = [[Vec3::ZERO; 3]; 7];
+        let mut t = 0.0;
+        let mut dt = (0.01 * dynamical_time(&p0, &state.m)).min(t_end);
+
+        while t < t_end {
+            check_step("Ias15Kernel", dt, t, t0);
+            let last = t + dt >= t_end;
+            if last {
+                let ratio = (t_end - t) / dt;
+                let mut q = ratio;
+                for k in 0..7 {
+                    b[k] = mul_same(&b[k], &Vec3::splat(q));
+                    q *= ratio;
+                }
+                dt = t_end - t;
+            }
+
+            let a0 = forces.calc_a_v(&p0, &v0);
+            let mut g = b_to_g(&b, &c);
+            let mut last_error = f64::INFINITY;
+            let mut a = a0;
+            for iteration in 0..MAX_ITERATIONS {
+                let b6_old = b[6];
+                for n in 1..8 {
+                    let p = Self::predict_p(&p0, &v0, &a0, &b, H[n], dt);
+                    let v = if state.post_newtonian.is_enabled() {
+                        Self::predict_v(&v0, &a0, &b, H[n], dt)
+                    } else {
+                        v0
+                    };
+                    a = forces.calc_a_v(&p, &v);
+                    // New divided difference g_{n-1} = [a_0, ..., a_n].
+                    let mut r = mul_same(&sub(&a, &a0), &Vec3::splat(1.0 / H[n]));
+                    for k in 0..(n - 1) {
+                        r = mul_same(&sub(&r, &g[k]), &Vec3::splat(1.0 / (H[n] - H[k + 1])));
+                    }
+                    g[n - 1] = r;
+                    b = g_to_b(&g, &c);
+                }
+                let error = max_norm(&sub(&b[6], &b6_old)) / max_norm(&a);
+                if error < 1e-16 || (iteration > 1 && error >= last_error) {
+                    break;
+                }
+                last_error = error;
+            }
+
+            let error_b = max_norm(&b[6]) / max_norm(&a);
+            let target = epsilon.max(gain * Self::roundoff(&p0, &a0, &state.m));
+            let mut dt_new = if error_b > 0.0 && error_b.is_finite() {
+                dt * (target / error_b).powf(1.0 / 7.0)
+            } else {
+                dt / SAFETY_FACTOR
+            };
+
+            if dt_new < SAFETY_FACTOR * dt {
+                // Reject, and rescale the polynomial to the shorter step.
+                stats.rejected += 1;
+                let ratio = dt_new / dt;
+                let mut q = ratio;
+                for k in 0..7 {
+                    b[k] = mul_same(&b[k], &Vec3::splat(q));
+                    q *= ratio;
+                }
+                dt = dt_new;
+                continue;
+            }
+            dt_new = dt_new.min(dt / SAFETY_FACTOR);
+
+            stats.accepted += 1;
+            if let Some(dense) = &mut dense {
+                // The step integrated up to the fraction s, as in predict_p
+                // and predict_v.
+                let mut c = vec![Phase::ZERO; 10];
+                c[0] = Phase { p: p0, v: v0 };
+                c[1] = Phase {
+                    p: mul_same(&v0, &Vec3::splat(dt)),
+                    v: mul_same(&a0, &Vec3::splat(dt)),
+                };
+                c[2].p = mul_same(&a0, &Vec3::splat(0.5 * dt * dt));
+                for k in 0..7 {
+                    let d = (k + 2) as f64;
+                    c[k + 3].p = mul_same(&b[k], &Vec3::splat(dt * dt / (d * (d + 1.0))));
+                    c[k + 2].v = mul_same(&b[k], &Vec3::splat(dt / d));
+                }
+                dense.push(t0 + t, dt, c);
+            }
+            let mut dp = mul_same(&a0, &Vec3::splat(0.5));
+            let mut dv = a0;
+            for k in 0..7 {
+                dp = advance(&dp, &b[k], &Vec3::splat(1.0 / ((k + 2) * (k + 3)) as f64));
+                dv = advance(&dv, &b[k], &Vec3::splat(1.0 / (k + 2) as f64));
+            }
+            let dp = advance(&mul_same(&v0, &Vec3::splat(dt)), &dp, &Vec3::splat(dt * dt));
+            let dv = mul_same(&dv, &Vec3::splat(dt));
+            compensated_add(&mut p0, &mut cp, &dp);
+            compensated_add(&mut v0, &mut cv, &dv);
+            t = if last { t_end } else { t + dt };
+
+            // Predict the next step's polynomial by re-expanding a(1 + q s).
+            let q = dt_new / dt;
+            let mut b_next = [[Vec3::ZERO; 3]; 7];
+            for i in 1..8 {
+                let qi = q.powi(i as i32);
+                for k in (i - 1)..7 {
+                    let binom = binomial(k + 1, i);
+                    b_next[i - 1] = advance(&b_next[i - 1], &b[k], &Vec3::splat(binom * qi));
+                }
+            }
+            b = b_next;
+            dt = dt_new;
+        }
+
+        let state = ThreeBodyState {
+            p: p0,
+            v: v0,
+            m: state.m,
+            t: state.t + duration,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        };
+        return (state, stats);
+    }
+}
+
+impl AdaptiveThreeBodyKernel for Ias15Kernel {
+    /// IAS15 has a single accuracy parameter; `tolerance.rtol` is used as its
+    /// `epsilon` and `tolerance.atol` is ignored.
+    fn kernel_adaptive(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+    ) -> (ThreeBodyState, StepStats) {
+        Self::integrate(state, duration, tolerance, None)
+    }
+
+    /// Uses the acceleration polynomial of each step, integrated like the step
+    /// itself, which is as accurate inside the step as at its end.
+    fn kernel_adaptive_dense(
+        state: ThreeBodyState,
+        duration: u64,
+        tolerance: Tolerance,
+    ) -> (ThreeBodyState, StepStats, DenseOutput) {
+        let mut dense = DenseOutput::default();
+        let (state, stats) = Self::integrate(state, duration, tolerance, Some(&mut dense));
+        return (state, stats, dense);
+    }
+}
+
+const TOLERANCE: Tolerance = Tolerance {
+    atol: 0.0,
+    rtol: EPSILON,
+};
+
+impl ThreeBodyKernel for Ias15Kernel {
+    fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
+        Self::kernel_adaptive(state, steps * dt, TOLERANCE).0
+    }
+
+    fn kernel_dense(state: ThreeBodyState, steps: u64, dt: u64) -> (ThreeBodyState, DenseOutput) {
+        let (state, _, dense) = Self::kernel_adaptive_dense(state, steps * dt, TOLERANCE);
+        return (state, dense);
+    }
+}
@@ -1,60 +1,138 @@
-use crate::Vec3;
+use std::marker::PhantomData;
+
+use crate::{Scalar, Vec3, Vector};
 
 use super::*;
 use crate::util;
 
-pub struct RK4Kernel;
+/// Classic 4th order Runge–Kutta at the precision of `V`, like
+/// `VelVerletKernel`.
+pub struct RK4Kernel<V: Vector = Vec3>(PhantomData<V>);
 
-impl ThreeBodyKernel for RK4Kernel {
-    fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
-        let dtf = dt as f64 * util::UNIT_TIME;
+impl<V: Vector> RK4Kernel<V> {
+    /// One step of length `dtf`, returning the new positions and velocities
+    /// and the four stage derivatives.
+    #[inline(always)]
+    fn step(
+        forces: &Forces<V>,
+        p: &[V; 3],
+        v: &[V; 3],
+        dtf: V::Scalar,
+    ) -> (Phase<V>, [Phase<V>; 4]) {
+        let c = V::Scalar::from_f64;
+        let dtm = V::splat(dtf);
+        let dtm2 = V::splat(dtf / c(2.0));
+        let dtm3 = V::splat(dtf / c(3.0));
+        let dtm6 = V::splat(dtf / c(6.0));
+
+        let k1r = *v;
+        let k1v = forces.calc_a_v(p, v);
+
+        let p1 = advance(p, v, &dtm2);
+        let k2r = advance(v, &k1v, &dtm2);
+        let k2v = forces.calc_a_v(&p1, &k2r);
+
+        let p2 = advance(p, &k2r, &dtm2);
+        let k3r = advance(v, &k2v, &dtm2);
+        let k3v = forces.calc_a_v(&p2, &k3r);
 
-        let original_m = state.m.clone();
-        let m = [
-            Vec3::splat(state.m[0] * util::GRAVITY_CONSTANT),
-            Vec3::splat(state.m[1] * util::GRAVITY_CONSTANT),
-            Vec3::splat(state.m[2] * util::GRAVITY_CONSTANT),
+        let p3 = advance(p, &k3r, &dtm);
+        let k4r = advance(v, &k3v, &dtm);
+        let k4v = forces.calc_a_v(&p3, &k4r);
+
+        let mut v1 = advance(v, &k1v, &dtm6);
+        v1 = advance(&v1, &k2v, &dtm3);
+        v1 = advance(&v1, &k3v, &dtm3);
+        v1 = advance(&v1, &k4v, &dtm6);
+
+        let mut p1 = advance(p, &k1r, &dtm6);
+        p1 = advance(&p1, &k2r, &dtm3);
+        p1 = advance(&p1, &k3r, &dtm3);
+        p1 = advance(&p1, &k4r, &dtm6);
+
+        let k = [
+            Phase { p: k1r, v: k1v },
+            Phase { p: k2r, v: k2v },
+            Phase { p: k3r, v: k3v },
+            Phase { p: k4r, v: k4v },
         ];
+        return (Phase { p: p1, v: v1 }, k);
+    }
+}
+
+impl<V: Vector> ThreeBodyKernel for RK4Kernel<V> {
+    fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
+        let units = units(&state);
+        let dtf = V::Scalar::from_f64(dt as f64 * util::UNIT_TIME / units.1);
 
-        let dtm = Vec3::splat(dtf);
-        let dtm2 = Vec3::splat(dtf / 2.0);
-        let dtm3 = Vec3::splat(dtf / 3.0);
-        let dtm6 = Vec3::splat(dtf / 6.0);
+        let forces = Forces::<V>::in_units(&state, units, 1.0);
 
-        let mut p = state.p;
-        let mut v = state.v;
+        let Phase { mut p, mut v } = Phase::<V>::in_units(&state, units);
 
         for _ in 0..steps {
-            let k1r = v;
-            let k1v = calc_a(&p, &m);
-
-            let p1 = advance(&p, &v, &dtm2);
-            let k2r = advance(&v, &k1v, &dtm2);
-            let k2v = calc_a(&p1, &m);
-
-            let p2 = advance(&p, &k2r, &dtm2);
-            let k3r = advance(&v, &k2v, &dtm2);
-            let k3v = calc_a(&p2, &m);
-
-            let p3 = advance(&p, &k3r, &dtm);
-            let k4r = advance(&v, &k3v, &dtm);
-            let k4v = calc_a(&p3, &m);
-
-            v = advance(&v, &k1v, &dtm6);
-            v = advance(&v, &k2v, &dtm3);
-            v = advance(&v, &k3v, &dtm3);
-            v = advance(&v, &k4v, &dtm6);
-
-            p = advance(&p, &k1r, &dtm6);
-            p = advance(&p, &k2r, &dtm3);
-            p = advance(&p, &k3r, &dtm3);
-            p = advance(&p, &k4r, &dtm6);
+            let (y, _) = Self::step(&forces, &p, &v, dtf);
+            p = y.p;
+            v = y.v;
         }
+        let y = Phase { p: p, v: v }.in_si(units);
         ThreeBodyState {
-            p: p,
-            v: v,
-            m: original_m,
+            p: y.p,
+            v: y.v,
+            m: state.m,
             t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
         }
     }
+
+    /// Uses the 3rd order continuous extension of RK4, with the weights
+    /// `b1 = s - 3/2 s^2 + 2/3 s^3`, `b2 = b3 = s^2 - 2/3 s^3` and
+    /// `b4 = -1/2 s^2 + 2/3 s^3` of the stage derivatives, at no extra cost.
+    fn kernel_dense(state: ThreeBodyState, steps: u64, dt: u64) -> (ThreeBodyState, DenseOutput) {
+        let c = V::Scalar::from_f64;
+        let units = units(&state);
+        let dtf_si = dt as f64 * util::UNIT_TIME;
+        let dtf = c(dtf_si / units.1);
+        let t0 = state.time();
+
+        let forces = Forces::<V>::in_units(&state, units, 1.0);
+
+        let mut dense = DenseOutput::default();
+        let mut y = Phase::<V>::in_units(&state, units);
+        for i in 0..steps {
+            let (y1, k) = Self::step(&forces, &y.p, &y.v, dtf);
+            let c2 = Phase::ZERO
+                .advance(&k[0], c(-1.5) * dtf)
+                .advance(&k[1], dtf)
+                .advance(&k[2], dtf)
+                .advance(&k[3], c(-0.5) * dtf);
+            let c3 = Phase::ZERO
+                .advance(&k[0], c(2.0 / 3.0) * dtf)
+                .advance(&k[1], c(-2.0 / 3.0) * dtf)
+                .advance(&k[2], c(-2.0 / 3.0) * dtf)
+                .advance(&k[3], c(2.0 / 3.0) * dtf);
+            let c1 = Phase::ZERO.advance(&k[0], dtf);
+            let coefficients = [y, c1, c2, c3].map(|c| c.in_si(units));
+            dense.push(t0 + i as f64 * dtf_si, dtf_si, coefficients.to_vec());
+            y = y1;
+        }
+        let y = y.in_si(units);
+        let state = ThreeBodyState {
+            p: y.p,
+            v: y.v,
+            m: state.m,
+            t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        };
+        return (state, dense);
+    }
 }
@@ -7,23 +7,22 @@ pub struct SymplecticEulerKernel;
 
 impl ThreeBodyKernel for SymplecticEulerKernel {
     fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
-        let original_m = state.m.clone();
+        state.assert_newtonian_only(
+            "SymplecticEulerKernel",
+            &[Extension::External, Extension::ForceLaw],
+        );
+        let original_m = state.m;
 
         let dtf = dt as f64 * util::UNIT_TIME;
-        let modified_g = util::GRAVITY_CONSTANT * dtf * dtf;
 
-        let m = [
-            Vec3::splat(state.m[0] * modified_g),
-            Vec3::splat(state.m[1] * modified_g),
-            Vec3::splat(state.m[2] * modified_g),
-        ];
+        let forces = Forces::scaled(&state, dtf * dtf);
         let mut p = state.p;
         let mut v = state.v;
         v[0] *= dtf;
         v[1] *= dtf;
         v[2] *= dtf;
         for _ in 0..steps {
-            let a = calc_a(&p, &m);
+            let a = forces.calc_a(&p);
             v = add(&v, &a);
             p = add(&p, &v);
         }
@@ -35,6 +34,12 @@ impl ThreeBodyKernel for SymplecticEulerKernel {
             v: v,
             m: original_m,
             t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
         }
     }
 }
@@ -43,16 +48,15 @@ pub struct SymplecticEulerRelativeKernel;
 
 impl ThreeBodyKernel for SymplecticEulerRelativeKernel {
     fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
-        let original_m = state.m.clone();
+        state.assert_newtonian_only(
+            "SymplecticEulerRelativeKernel",
+            &[Extension::External, Extension::ForceLaw],
+        );
+        let original_m = state.m;
 
         let dtf = dt as f64 * util::UNIT_TIME;
-        let modified_g = util::GRAVITY_CONSTANT * dtf * dtf;
 
-        let m = [
-            Vec3::splat(state.m[0] * modified_g),
-            Vec3::splat(state.m[1] * modified_g),
-            Vec3::splat(state.m[2] * modified_g),
-        ];
+        let forces = Forces::scaled(&state, dtf * dtf);
         let mut p0 = state.p;
         let mut v0 = [state.v[0] * dtf, state.v[1] * dtf, state.v[2] * dtf];
 
@@ -60,7 +64,7 @@ impl ThreeBodyKernel for SymplecticEulerRelativeKernel {
         let mut v = [Vec3::ZERO; 3];
 
         for _ in 0..steps {
-            let a = calc_a(&add(&p, &p0), &m);
+            let a = forces.calc_a(&add(&p, &p0));
             v = add(&v, &a);
             p = add(&p, &v0);
             p = add(&p, &v);
@@ -78,6 +82,61 @@ impl ThreeBodyKernel for SymplecticEulerRelativeKernel {
             v: v0,
             m: original_m,
             t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        }
+    }
+}
+
+/// `SymplecticEulerRelativeKernel` with the offsets from the initial state
+/// summed by Kahan summation.
+pub struct SymplecticEulerCompensatedKernel;
+
+impl ThreeBodyKernel for SymplecticEulerCompensatedKernel {
+    fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
+        state.assert_newtonian_only(
+            "SymplecticEulerCompensatedKernel",
+            &[Extension::External, Extension::ForceLaw],
+        );
+        let original_m = state.m;
+
+        let dtf = dt as f64 * util::UNIT_TIME;
+
+        let forces = Forces::scaled(&state, dtf * dtf);
+        let p0 = state.p;
+        let mut v0 = [state.v[0] * dtf, state.v[1] * dtf, state.v[2] * dtf];
+
+        let mut p = [Vec3::ZERO; 3];
+        let mut v = [Vec3::ZERO; 3];
+        let mut cp = [Vec3::ZERO; 3];
+        let mut cv = [Vec3::ZERO; 3];
+
+        for _ in 0..steps {
+            let a = forces.calc_a(&add(&p, &p0));
+            compensated_add(&mut v, &mut cv, &a);
+            compensated_add(&mut p, &mut cp, &add(&v0, &sub(&v, &cv)));
+        }
+
+        v0 = add(&v0, &sub(&v, &cv));
+        v0[0] /= dtf;
+        v0[1] /= dtf;
+        v0[2] /= dtf;
+
+        ThreeBodyState {
+            p: add(&p0, &sub(&p, &cp)),
+            v: v0,
+            m: original_m,
+            t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
         }
     }
 }
@@ -0,0 +1,209 @@
+use crate::kernels::advance_time;
+use crate::util;
+use crate::Vec3;
+
+use super::*;
+
+const PAIRS: [(usize, usize); 3] = [(0, 1), (1, 2), (0, 2)];
+
+/// Time transformed leapfrog combining the logarithmic Hamiltonian (Mikkola &
+/// Tanikawa 1999) and TTL (Mikkola & Aarseth 2002) transformations, for
+/// highly eccentric orbits.
+///
+/// The leapfrog `D(h/2) K(h) D(h/2)` takes equal steps `h` in a fictitious
+/// time `s`. A drift moves the positions and the physical time over
+/// `h / (T + B + W)` and a kick the velocities over `h / (U + Omega)`, where
+/// `B` is the binding energy and `W` follows `Omega` along the solution.
+/// Both denominators equal `U + Omega` on the exact solution, so the physical
+/// step shrinks in proportion to the separation of the closest pair while the
+/// map stays symplectic and time symmetric. With the logarithmic Hamiltonian
+/// alone, an isolated Kepler orbit even keeps its shape at any step size.
+/// As in `ChainKernel`, pairs with negligible mass are left to `Omega`, here
+/// weighted by the heavier mass so that two test particles, which do not
+/// interact, do not shorten the step.
+///
+/// `dt` sets the fictitious step to the one taking `dt` ticks at the start.
+/// The physical time taken by `steps` steps is then not a whole number of
+/// ticks, and the remainder is kept in `t_frac`.
+pub struct TimeTransformedKernel;
+
+/// Masses and the constants of the time transformation.
+struct System {
+    m: [f64; 3],
+    mu: [f64; 3],
+    omega: [f64; 3],
+    binding_energy: f64,
+    softening: Softening,
+}
+
+impl System {
+    fn new(m: &[f64; 3], softening: Softening) -> System {
+        let mean = (m[0] + m[1] + m[2]) / 3.0;
+        let mut omega = [0.0; 3];
+        for (k, &(i, j)) in PAIRS.iter().enumerate() {
+            if m[i] * m[j] < SMALL_MASS * mean * mean {
+                omega[k] = util::GRAVITY_CONSTANT * mean * m[i].max(m[j]);
+            }
+        }
+        System {
+            m: *m,
+            mu: m.map(|m| m * util::GRAVITY_CONSTANT),
+            omega: omega,
+            binding_energy: 0.0,
+            softening: softening,
+        }
+    }
+
+    fn kinetic_energy(&self, v: &[Vec3; 3]) -> f64 {
+        let mut e_k = 0.0;
+        for i in 0..3 {
+            e_k += 0.5 * self.m[i] * v[i].norm_squared();
+        }
+        return e_k;
+    }
+
+    /// Potential energy `U` (positive) and the TTL function `Omega`.
+    fn potential(&self, p: &[Vec3; 3]) -> (f64, f64) {
+        let mut u = 0.0;
+        let mut omega = 0.0;
+        for (k, &(i, j)) in PAIRS.iter().enumerate() {
+            let r2 = (p[j] - p[i]).norm_squared();
+            u += self.mu[i] * self.m[j] * self.softening.potential(r2);
+            omega += self.omega[k] / r2.sqrt();
+        }
+        return (u, omega);
+    }
+
+    /// Drifts the positions over `h` in `s`, returning the physical time
+    /// taken.
+    #[inline(always)]
+    fn drift(&self, p: &mut [Vec3; 3], v: &[Vec3; 3], w: f64, h: f64) -> f64 {
+        let dt = h / (self.kinetic_energy(v) + self.binding_energy + w);
+        *p = advance(p, v, &Vec3::splat(dt));
+        return dt;
+    }
+
+    /// Kicks the velocities over `h` in `s` and updates `W`.
+    #[inline(always)]
+    fn kick(&self, p: &[Vec3; 3], v: &mut [Vec3; 3], w: &mut f64, h: f64) {
+        let mut u = 0.0;
+        let mut omega = 0.0;
+        let mut a = [Vec3::ZERO; 3];
+        // Gradient of Omega with respect to the positions.
+        let mut g = [Vec3::ZERO; 3];
+        for (k, &(i, j)) in PAIRS.iter().enumerate() {
+            let r = p[j] - p[i];
+            let r2 = r.norm_squared();
+            let inv = 1.0 / r2.sqrt();
+            // Omega is not softened, it only sets the time transformation.
+            let f = r * (inv * inv * inv);
+            let fs = r * self.softening.force(r2);
+            u += self.mu[i] * self.m[j] * self.softening.potential(r2);
+            omega += self.omega[k] * inv;
+            a[i] = Vec3::mul_add(fs, Vec3::splat(self.mu[j]), a[i]);
+            a[j] = Vec3::mul_neg_add(fs, Vec3::splat(self.mu[i]), a[j]);
+            g[i] = Vec3::mul_add(f, Vec3::splat(self.omega[k]), g[i]);
+            g[j] = Vec3::mul_neg_add(f, Vec3::splat(self.omega[k]), g[j]);
+        }
+        let dt = h / (u + omega);
+        let v0 = *v;
+        *v = advance(v, &a, &Vec3::splat(dt));
+        // dW/dt = grad Omega . v, with the velocity averaged over the kick to
+        // keep the map symmetric.
+        let mut dw = 0.0;
+        for i in 0..3 {
+            dw += (g[i] * (v0[i] + v[i])).reduce_add() / 2.0;
+        }
+        *w += dt * dw;
+    }
+}
+
+impl ThreeBodyKernel for TimeTransformedKernel {
+    fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
+        state.assert_newtonian_only("TimeTransformedKernel", &[]);
+        let mut system = System::new(&state.m, state.softening);
+        let mut p = state.p;
+        let mut v = state.v;
+        let (u, omega) = system.potential(&p);
+        system.binding_energy = u - system.kinetic_energy(&v);
+        let mut w = omega;
+        let h = dt as f64 * util::UNIT_TIME * (u + omega);
+
+        let mut elapsed = 0.0;
+        if steps > 0 {
+            elapsed += system.drift(&mut p, &v, w, h / 2.0);
+        }
+        for i in 0..steps {
+            system.kick(&p, &mut v, &mut w, h);
+            let hd = if i + 1 == steps { h / 2.0 } else { h };
+            elapsed += system.drift(&mut p, &v, w, hd);
+        }
+
+        let (t, t_frac) = advance_time(state.t, state.t_frac, elapsed);
+        ThreeBodyState {
+            p: p,
+            v: v,
+            m: state.m,
+            t: t,
+            t_frac: t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        }
+    }
+
+    /// Runs the drifts of consecutive steps separately, since the default
+    /// would restart the time transformation every step, and interpolates
+    /// each step in physical time by cubic Hermite polynomials.
+    fn kernel_dense(state: ThreeBodyState, steps: u64, dt: u64) -> (ThreeBodyState, DenseOutput) {
+        state.assert_newtonian_only("TimeTransformedKernel", &[]);
+        let mut system = System::new(&state.m, state.softening);
+        let mut p = state.p;
+        let mut v = state.v;
+        let (u, omega) = system.potential(&p);
+        system.binding_energy = u - system.kinetic_energy(&v);
+        let mut w = omega;
+        let h = dt as f64 * util::UNIT_TIME * (u + omega);
+        let m = state.m.map(|m| Vec3::splat(m * util::GRAVITY_CONSTANT));
+
+        let mut dense = DenseOutput::default();
+        let t0 = state.time();
+        let mut elapsed = 0.0;
+        let mut y = Phase { p: p, v: v };
+        let mut f = Phase {
+            p: v,
+            v: calc_a(&p, &m, &state.softening),
+        };
+        for _ in 0..steps {
+            let mut dt = system.drift(&mut p, &v, w, h / 2.0);
+            system.kick(&p, &mut v, &mut w, h);
+            dt += system.drift(&mut p, &v, w, h / 2.0);
+            let y1 = Phase { p: p, v: v };
+            let f1 = Phase {
+                p: v,
+                v: calc_a(&p, &m, &state.softening),
+            };
+            dense.push_hermite(t0 + elapsed, dt, &y, &f, &y1, &f1);
+            elapsed += dt;
+            y = y1;
+            f = f1;
+        }
+
+        let (t, t_frac) = advance_time(state.t, state.t_frac, elapsed);
+        let state = ThreeBodyState {
+            p: p,
+            v: v,
+            m: state.m,
+            t: t,
+            t_frac: t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        };
+        return (state, dense);
+    }
+}
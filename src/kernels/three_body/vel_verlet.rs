@@ -1,44 +1,58 @@
+use std::marker::PhantomData;
+
 use crate::util;
-use crate::Vec3;
+use crate::{Scalar, Vec3, Vector};
 
 use super::*;
 
-pub struct VelVerletKernel;
+/// Velocity Verlet at the precision of `V`, `Vec3` by default, with `Vec3f`
+/// for quick runs in single precision and `Vec3DD` for reference solutions
+/// beyond the round-off of `f64`. It works in the units of `natural_scales`,
+/// which keeps `f32` in range and changes nothing in `f64`.
+pub struct VelVerletKernel<V: Vector = Vec3>(PhantomData<V>);
 
-impl ThreeBodyKernel for VelVerletKernel {
+impl<V: Vector> ThreeBodyKernel for VelVerletKernel<V> {
     fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
-        let original_m = state.m.clone();
+        state.assert_newtonian_only(
+            "VelVerletKernel",
+            &[Extension::External, Extension::ForceLaw],
+        );
+        let original_m = state.m;
 
-        let dtf = dt as f64 * util::UNIT_TIME;
-        let modified_g = util::GRAVITY_CONSTANT * dtf * dtf;
-
-        let m = [
-            Vec3::splat(state.m[0] * modified_g),
-            Vec3::splat(state.m[1] * modified_g),
-            Vec3::splat(state.m[2] * modified_g),
-        ];
-        let mut p = state.p;
-        let mut v = state.v;
-        v[0] *= dtf;
-        v[1] *= dtf;
-        v[2] *= dtf;
-        let mut a = mul_same(&calc_a(&p, &m), &Vec3::splat(0.5));
+        let units = units(&state);
+        let dtf = dt as f64 * util::UNIT_TIME / units.1;
+
+        let forces = Forces::<V>::in_units(&state, units, dtf * dtf);
+        let Phase { mut p, mut v } = Phase::<V>::in_units(&state, units);
+        let dtf = V::Scalar::from_f64(dtf);
+        v[0] = v[0] * dtf;
+        v[1] = v[1] * dtf;
+        v[2] = v[2] * dtf;
+        let half = V::splat(V::Scalar::from_f64(0.5));
+        let mut a = mul_same(&forces.calc_a(&p), &half);
         for _ in 0..steps {
             p = add(&p, &v);
             p = add(&p, &a);
-            let a2 = mul_same(&calc_a(&p, &m), &Vec3::splat(0.5));
+            let a2 = mul_same(&forces.calc_a(&p), &half);
             v = add(&v, &a);
             v = add(&v, &a2);
             a = a2;
         }
-        v[0] /= dtf;
-        v[1] /= dtf;
-        v[2] /= dtf;
+        v[0] = v[0] / dtf;
+        v[1] = v[1] / dtf;
+        v[2] = v[2] / dtf;
+        let y = Phase { p: p, v: v }.in_si(units);
         ThreeBodyState {
-            p: p,
-            v: v,
+            p: y.p,
+            v: y.v,
             m: original_m,
             t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
         }
     }
 }
@@ -47,28 +61,27 @@ pub struct VelVerletRelativeKernel;
 
 impl ThreeBodyKernel for VelVerletRelativeKernel {
     fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
-        let original_m = state.m.clone();
+        state.assert_newtonian_only(
+            "VelVerletRelativeKernel",
+            &[Extension::External, Extension::ForceLaw],
+        );
+        let original_m = state.m;
 
         let dtf = dt as f64 * util::UNIT_TIME;
-        let modified_g = util::GRAVITY_CONSTANT * dtf * dtf;
 
-        let m = [
-            Vec3::splat(state.m[0] * modified_g),
-            Vec3::splat(state.m[1] * modified_g),
-            Vec3::splat(state.m[2] * modified_g),
-        ];
+        let forces = Forces::scaled(&state, dtf * dtf);
         let mut p0 = state.p;
         let mut v0 = [state.v[0] * dtf, state.v[1] * dtf, state.v[2] * dtf];
 
         let mut p = [Vec3::ZERO; 3];
         let mut v = [Vec3::ZERO; 3];
 
-        let mut a = mul_same(&calc_a(&p0, &m), &Vec3::splat(0.5));
+        let mut a = mul_same(&forces.calc_a(&p0), &Vec3::splat(0.5));
         for _ in 0..steps {
             p = add(&p, &v);
             p = add(&p, &v0);
             p = add(&p, &a);
-            let a2 = mul_same(&calc_a(&add(&p0, &p), &m), &Vec3::splat(0.5));
+            let a2 = mul_same(&forces.calc_a(&add(&p0, &p)), &Vec3::splat(0.5));
             v = add(&v, &a);
             v = add(&v, &a2);
             a = a2;
@@ -86,6 +99,65 @@ impl ThreeBodyKernel for VelVerletRelativeKernel {
             v: v0,
             m: original_m,
             t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        }
+    }
+}
+
+/// `VelVerletRelativeKernel` with the offsets from the initial state summed
+/// by Kahan summation, so that the rounding errors of the many small
+/// increments do not build up over long runs.
+pub struct VelVerletCompensatedKernel;
+
+impl ThreeBodyKernel for VelVerletCompensatedKernel {
+    fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
+        state.assert_newtonian_only(
+            "VelVerletCompensatedKernel",
+            &[Extension::External, Extension::ForceLaw],
+        );
+        let original_m = state.m;
+
+        let dtf = dt as f64 * util::UNIT_TIME;
+
+        let forces = Forces::scaled(&state, dtf * dtf);
+        let p0 = state.p;
+        let mut v0 = [state.v[0] * dtf, state.v[1] * dtf, state.v[2] * dtf];
+
+        let mut p = [Vec3::ZERO; 3];
+        let mut v = [Vec3::ZERO; 3];
+        let mut cp = [Vec3::ZERO; 3];
+        let mut cv = [Vec3::ZERO; 3];
+
+        let mut a = mul_same(&forces.calc_a(&p0), &Vec3::splat(0.5));
+        for _ in 0..steps {
+            let dp = add(&add(&v0, &sub(&v, &cv)), &a);
+            compensated_add(&mut p, &mut cp, &dp);
+            let a2 = mul_same(&forces.calc_a(&add(&p0, &p)), &Vec3::splat(0.5));
+            compensated_add(&mut v, &mut cv, &add(&a, &a2));
+            a = a2;
+        }
+
+        v0 = add(&v0, &sub(&v, &cv));
+        v0[0] /= dtf;
+        v0[1] /= dtf;
+        v0[2] /= dtf;
+
+        ThreeBodyState {
+            p: add(&p0, &sub(&p, &cp)),
+            v: v0,
+            m: original_m,
+            t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
         }
     }
 }
@@ -1,8 +1,7 @@
-use core::mem::transmute;
-use std::str;
+use std::marker::PhantomData;
 
 use super::*;
-use crate::{util, Vec3};
+use crate::{util, Scalar, Vec3, Vector};
 
 // w0 = -2^(1/3)/(2-2^(1/3))
 //    ~= -1.702414383919315268095375617942921653843998752434289656657411995...
@@ -12,77 +11,98 @@ use crate::{util, Vec3};
 //    ~= 0xbffb3d16dd72c672
 
 // d1 = w1
-const D1: f64 = unsafe { transmute::<u64, f64>(0x3ff59e8b6eb96339) };
+pub(crate) const D1: f64 = f64::from_bits(0x3ff59e8b6eb96339);
 // d2 = w0
-const D2: f64 = unsafe { transmute::<u64, f64>(0xbffb3d16dd72c672) };
+pub(crate) const D2: f64 = f64::from_bits(0xbffb3d16dd72c672);
 // d3 = w1
-const D3: f64 = D1;
+pub(crate) const D3: f64 = D1;
 
 // c1 = w1/2
 //    ~= 0.6756035959798288170238439044857304134609996881085724141643529988...
 //    ~= 0x3fe59e8b6eb96339
-const C1: f64 = unsafe { transmute::<u64, f64>(0x3fe59e8b6eb96339) };
+pub(crate) const C1: f64 = f64::from_bits(0x3fe59e8b6eb96339);
 
 // c2 = (w0+w1)/2
 //    ~= -0.175603595979828817023843904485730413460999688108572414164352998...
 //    ~=  0xbfc67a2dbae58ce4
-const C2: f64 = unsafe { transmute::<u64, f64>(0xbfc67a2dbae58ce4) };
+pub(crate) const C2: f64 = f64::from_bits(0xbfc67a2dbae58ce4);
 
 // c3 = (w0+w1)/2
-const C3: f64 = C2;
+pub(crate) const C3: f64 = C2;
 
 // c4 = w1/2
-const C4: f64 = C1;
+pub(crate) const C4: f64 = C1;
+
+/// Yoshida's weights `w1 = 1 / (2 - 2^(1/3))` and `w0 = 1 - 2 w1` at the
+/// precision of `S`, the cube root refined by a Newton step from the `f64`
+/// one, so that the order conditions hold to that precision. In `f64` they
+/// are `D1` and `D2`.
+pub fn yoshida4_weights<S: Scalar>() -> (S, S) {
+    let one = S::from_f64(1.0);
+    let two = S::from_f64(2.0);
+    let x = S::from_f64(2f64.cbrt());
+    let x = x - (x * x * x - two) / (S::from_f64(3.0) * x * x);
+    let w1 = one / (two - x);
+    return (w1, one - two * w1);
+}
 
-pub struct Yoshida4Kernel;
+/// 4th order Yoshida at the precision of `V`, like `VelVerletKernel`, with
+/// its coefficients computed at that precision.
+pub struct Yoshida4Kernel<V: Vector = Vec3>(PhantomData<V>);
 
-impl ThreeBodyKernel for Yoshida4Kernel {
+impl<V: Vector> ThreeBodyKernel for Yoshida4Kernel<V> {
     fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
-        let dtf = dt as f64 * util::UNIT_TIME;
+        state.assert_newtonian_only(
+            "Yoshida4Kernel",
+            &[Extension::External, Extension::ForceLaw],
+        );
+        let units = units(&state);
+        let dtf = V::Scalar::from_f64(dt as f64 * util::UNIT_TIME / units.1);
 
-        let original_m = state.m.clone();
-        let m = [
-            Vec3::splat(state.m[0] * util::GRAVITY_CONSTANT),
-            Vec3::splat(state.m[1] * util::GRAVITY_CONSTANT),
-            Vec3::splat(state.m[2] * util::GRAVITY_CONSTANT),
-        ];
+        let original_m = state.m;
+        let forces = Forces::<V>::in_units(&state, units, 1.0);
 
-        let c1 = Vec3::splat(C1 * dtf);
-        let c2 = Vec3::splat(C2 * dtf);
-        let c3 = Vec3::splat(C3 * dtf);
-        let c4 = Vec3::splat(C4 * dtf);
+        let (w1, w0) = yoshida4_weights::<V::Scalar>();
+        let half = V::Scalar::from_f64(0.5);
+        let c1 = V::splat(w1 * half * dtf);
+        let c2 = V::splat((w0 + w1) * half * dtf);
+        let c3 = c2;
+        let c4 = c1;
 
-        let d1 = Vec3::splat(D1 * dtf);
-        let d2 = Vec3::splat(D2 * dtf);
-        let d3 = Vec3::splat(D3 * dtf);
+        let d1 = V::splat(w1 * dtf);
+        let d2 = V::splat(w0 * dtf);
+        let d3 = d1;
 
-        // println!("{} {} {} {}", c1, c2, c3, c4);
-        // println!("{} {} {}", d1, d2, d3);
-
-        let mut p = state.p;
-        let mut v = state.v;
+        let Phase { mut p, mut v } = Phase::<V>::in_units(&state, units);
 
         for _ in 0..steps {
             p = advance(&p, &v, &c1);
-            let a = calc_a(&p, &m);
+            let a = forces.calc_a(&p);
             v = advance(&v, &a, &d1);
 
             p = advance(&p, &v, &c2);
-            let a = calc_a(&p, &m);
+            let a = forces.calc_a(&p);
             v = advance(&v, &a, &d2);
 
             p = advance(&p, &v, &c3);
-            let a = calc_a(&p, &m);
+            let a = forces.calc_a(&p);
             v = advance(&v, &a, &d3);
 
             p = advance(&p, &v, &c4);
         }
 
+        let y = Phase { p: p, v: v }.in_si(units);
         ThreeBodyState {
-            p: p,
-            v: v,
+            p: y.p,
+            v: y.v,
             m: original_m,
             t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
         }
     }
 }
@@ -91,14 +111,14 @@ pub struct Yoshida4RelativeKernel;
 
 impl ThreeBodyKernel for Yoshida4RelativeKernel {
     fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
+        state.assert_newtonian_only(
+            "Yoshida4RelativeKernel",
+            &[Extension::External, Extension::ForceLaw],
+        );
         let dtf = dt as f64 * util::UNIT_TIME;
 
-        let original_m = state.m.clone();
-        let m = [
-            Vec3::splat(state.m[0] * util::GRAVITY_CONSTANT),
-            Vec3::splat(state.m[1] * util::GRAVITY_CONSTANT),
-            Vec3::splat(state.m[2] * util::GRAVITY_CONSTANT),
-        ];
+        let original_m = state.m;
+        let forces = Forces::new(&state);
 
         let c1 = Vec3::splat(C1 * dtf);
         let c2 = Vec3::splat(C2 * dtf);
@@ -119,15 +139,15 @@ impl ThreeBodyKernel for Yoshida4RelativeKernel {
 
         for _ in 0..steps {
             p = advance(&p, &add(&v0, &v), &c1);
-            let a = calc_a(&add(&p0, &p), &m);
+            let a = forces.calc_a(&add(&p0, &p));
             v = advance(&v, &a, &d1);
 
             p = advance(&p, &add(&v0, &v), &c2);
-            let a = calc_a(&add(&p0, &p), &m);
+            let a = forces.calc_a(&add(&p0, &p));
             v = advance(&v, &a, &d2);
 
             p = advance(&p, &add(&v0, &v), &c3);
-            let a = calc_a(&add(&p0, &p), &m);
+            let a = forces.calc_a(&add(&p0, &p));
             v = advance(&v, &a, &d3);
 
             p = advance(&p, &add(&v0, &v), &c4);
@@ -138,6 +158,78 @@ impl ThreeBodyKernel for Yoshida4RelativeKernel {
             v: add(&v0, &v),
             m: original_m,
             t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
+        }
+    }
+}
+
+/// `Yoshida4RelativeKernel` with the offsets from the initial state summed
+/// by Kahan summation.
+pub struct Yoshida4CompensatedKernel;
+
+impl ThreeBodyKernel for Yoshida4CompensatedKernel {
+    fn kernel(state: ThreeBodyState, steps: u64, dt: u64) -> ThreeBodyState {
+        state.assert_newtonian_only(
+            "Yoshida4CompensatedKernel",
+            &[Extension::External, Extension::ForceLaw],
+        );
+        let dtf = dt as f64 * util::UNIT_TIME;
+
+        let original_m = state.m;
+        let forces = Forces::new(&state);
+
+        let c1 = Vec3::splat(C1 * dtf);
+        let c2 = Vec3::splat(C2 * dtf);
+        let c3 = Vec3::splat(C3 * dtf);
+        let c4 = Vec3::splat(C4 * dtf);
+
+        let d1 = Vec3::splat(D1 * dtf);
+        let d2 = Vec3::splat(D2 * dtf);
+        let d3 = Vec3::splat(D3 * dtf);
+
+        let p0 = state.p;
+        let v0 = state.v;
+        let mut p = [Vec3::ZERO; 3];
+        let mut v = [Vec3::ZERO; 3];
+        let mut cp = [Vec3::ZERO; 3];
+        let mut cv = [Vec3::ZERO; 3];
+
+        for _ in 0..steps {
+            let dp = mul_same(&add(&v0, &sub(&v, &cv)), &c1);
+            compensated_add(&mut p, &mut cp, &dp);
+            let a = forces.calc_a(&add(&p0, &p));
+            compensated_add(&mut v, &mut cv, &mul_same(&a, &d1));
+
+            let dp = mul_same(&add(&v0, &sub(&v, &cv)), &c2);
+            compensated_add(&mut p, &mut cp, &dp);
+            let a = forces.calc_a(&add(&p0, &p));
+            compensated_add(&mut v, &mut cv, &mul_same(&a, &d2));
+
+            let dp = mul_same(&add(&v0, &sub(&v, &cv)), &c3);
+            compensated_add(&mut p, &mut cp, &dp);
+            let a = forces.calc_a(&add(&p0, &p));
+            compensated_add(&mut v, &mut cv, &mul_same(&a, &d3));
+
+            let dp = mul_same(&add(&v0, &sub(&v, &cv)), &c4);
+            compensated_add(&mut p, &mut cp, &dp);
+        }
+
+        ThreeBodyState {
+            p: add(&p0, &sub(&p, &cp)),
+            v: add(&v0, &sub(&v, &cv)),
+            m: original_m,
+            t: state.t + steps * dt,
+            t_frac: state.t_frac,
+            softening: state.softening,
+            post_newtonian: state.post_newtonian,
+            external: state.external,
+            force_law: state.force_law,
+            events: state.events,
         }
     }
 }
@@ -2,7 +2,7 @@
 
 use std::ops::*;
 
-use crate::Vec3;
+use crate::{DoubleDouble, Vec3, Vec3DD, Vec3f};
 
 macro_rules! bulk_impl_op_ref_self_for {
     ($(($op:ident, $method:ident, $tself:ty) => [$($t:ty),+]),+ $(,)?) => {
@@ -63,6 +63,18 @@ bulk_impl_op_ref_self_for! {
     (Sub, sub, Vec3) => [Vec3],
     (Mul, mul, Vec3) => [Vec3, f64],
     (Div, div, Vec3) => [Vec3, f64],
+    (Add, add, Vec3f) => [Vec3f],
+    (Sub, sub, Vec3f) => [Vec3f],
+    (Mul, mul, Vec3f) => [Vec3f, f32],
+    (Div, div, Vec3f) => [Vec3f, f32],
+    (Add, add, Vec3DD) => [Vec3DD],
+    (Sub, sub, Vec3DD) => [Vec3DD],
+    (Mul, mul, Vec3DD) => [Vec3DD, DoubleDouble],
+    (Div, div, Vec3DD) => [Vec3DD, DoubleDouble],
+    (Add, add, DoubleDouble) => [DoubleDouble],
+    (Sub, sub, DoubleDouble) => [DoubleDouble],
+    (Mul, mul, DoubleDouble) => [DoubleDouble, f64],
+    (Div, div, DoubleDouble) => [DoubleDouble, f64],
 }
 
 bulk_impl_op_assign_for! {
@@ -72,4 +84,20 @@ bulk_impl_op_assign_for! {
     (MulAssign<Vec3>, mul, mul_assign) => [Vec3],
     (DivAssign<f64>, div, div_assign) => [Vec3],
     (DivAssign<Vec3>, div, div_assign) => [Vec3],
+    (AddAssign<Vec3f>, add, add_assign) => [Vec3f],
+    (SubAssign<Vec3f>, sub, sub_assign) => [Vec3f],
+    (MulAssign<f32>, mul, mul_assign) => [Vec3f],
+    (MulAssign<Vec3f>, mul, mul_assign) => [Vec3f],
+    (DivAssign<f32>, div, div_assign) => [Vec3f],
+    (DivAssign<Vec3f>, div, div_assign) => [Vec3f],
+    (AddAssign<Vec3DD>, add, add_assign) => [Vec3DD],
+    (SubAssign<Vec3DD>, sub, sub_assign) => [Vec3DD],
+    (MulAssign<DoubleDouble>, mul, mul_assign) => [Vec3DD],
+    (MulAssign<Vec3DD>, mul, mul_assign) => [Vec3DD],
+    (DivAssign<DoubleDouble>, div, div_assign) => [Vec3DD],
+    (DivAssign<Vec3DD>, div, div_assign) => [Vec3DD],
+    (AddAssign<DoubleDouble>, add, add_assign) => [DoubleDouble],
+    (SubAssign<DoubleDouble>, sub, sub_assign) => [DoubleDouble],
+    (MulAssign<DoubleDouble>, mul, mul_assign) => [DoubleDouble],
+    (DivAssign<DoubleDouble>, div, div_assign) => [DoubleDouble],
 }
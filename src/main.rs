@@ -1,14 +1,24 @@
+#![allow(
+    clippy::needless_return,
+    clippy::redundant_field_names,
+    clippy::needless_range_loop
+)]
+
+mod double_double;
 pub mod kernels;
 mod macros;
 pub mod test;
 pub mod util;
 mod vec3;
+mod vec3f;
+mod vector;
 pub mod viewer;
 
-use std::time::Instant;
-
+pub use double_double::*;
 use kernels::three_body::*;
 pub use vec3::*;
+pub use vec3f::*;
+pub use vector::*;
 
 fn main() {
     // let p = vec![
@@ -45,22 +55,97 @@ fn main() {
         Vec3::new(10000.0, 0.0, 0.0),
     ];
     let m = vec![2e30, 0.0, 0.0];
-    let mut state = kernels::PhysicsState { p, v, m, t: 0 };
+    let state = kernels::PhysicsState {
+        p,
+        v,
+        m,
+        r: vec![0.0; 3],
+        n_test: 0,
+        t: 0,
+        t_frac: 0.0,
+        softening: kernels::Softening::None,
+        post_newtonian: kernels::PostNewtonian::default(),
+        external: vec![],
+        force_law: None,
+        events: vec![],
+    };
     // state.normalize();
 
     // let mut ground_truth = state.clone();
     // simulate(yoshida4_relative_kernel, &mut ground_truth, 10000, 10000, 1);
 
+    // test::test_barnes_hut(&test::plummer_sphere(100000, 0), &[0.3, 0.5, 0.7, 1.0]);
+    // test::test_simd_direct(&test::plummer_sphere(20000, 0), &[1, 2, 4, 8], 4);
+    // test::test_fmm(&test::plummer_sphere(100000, 0), &[2, 4, 6, 8, 10], 0.5);
+    // test::test_ks_regularization(&state, 2e10, &[1 << 16, 1 << 20, 1 << 23], 1 << 25);
+    // test::test_pythagorean(&[1e-8, 1e-10, 1e-12, 1e-13, 1e-14]);
+    // test::test_hermite(&test::plummer_sphere(100, 0), &[0.04, 0.02, 0.01], 1 << 56, 1 << 60);
+    // test::test_softening(&test::plummer_sphere(100, 0), 1e15, &[1 << 48, 1 << 46, 1 << 44], 1 << 56);
+    // test::test_collisions(&test::plummer_sphere(100, 0), 1e15, 1 << 44, 1 << 56);
+    // test::test_perihelion_precession(1);
+    // test::test_inspiral(1e6);
+    // test::test_external_potentials(&test::plummer_sphere(100, 0), 1 << 48, 1 << 62);
+    // test::test_force_laws(1000, 10);
+    // test::test_test_particles(&test::asteroid_belt(1000, 0), 1 << 30, 1 << 40);
+    // test::test_dense_output::<Yoshida4Kernel>(&state, 1 << 10, 1 << 19, 1000);
+    // test::test_dense_output::<Ias15Kernel>(&state, 1 << 10, 1 << 19, 1000);
+    // test::test_dense_output::<ChainKernel>(&state, 1 << 10, 1 << 19, 1000);
+    // test::test_adaptive_dense_output::<BulirschStoerKernel>(&state, 1 << 25, Tolerance::default(), 1000);
+    // test::test_events::<Yoshida4Kernel>([0.9, 0.5], 1000, 10);
+    // test::test_events::<Ias15Kernel>([0.9, 0.5], 1000, 10);
+    // test::test_double_double(&state, 1 << 25);
+    // test::test_precision(&state, &[1 << 4, 1 << 8], 1 << 25);
+    // test::test_precision(&test::plummer_sphere(64, 42), &[1 << 34, 1 << 36], 1 << 44);
+    // test::test_time_transformed([0.9, 0.99], &[100, 1000, 10000, 100000], 10);
+    // test::compare_kernels::<Yoshida4Kernel, ForestRuthKernel>(&state, 1 << 22, 1 << 4);
+    // test::compare_kernels::<Yoshida4RelativeKernel, ForestRuthRelativeKernel>(&state, 1 << 22, 1 << 4);
+
     // viewer::start_viewer::<Yoshida4RelativeKernel>(state, 100, 200000);
 
+    test::test_error::<Yoshida4CompensatedKernel>(&state, "analysis/yoshida4_compensated.json");
     test::test_error::<Yoshida4RelativeKernel>(&state, "analysis/yoshida4_relative.json");
     test::test_error::<Yoshida4Kernel>(&state, "analysis/yoshida4.json");
+    test::test_error::<Yoshida6RelativeKernel>(&state, "analysis/yoshida6_relative.json");
+    test::test_error::<Yoshida6Kernel>(&state, "analysis/yoshida6.json");
+    test::test_error::<Yoshida8RelativeKernel>(&state, "analysis/yoshida8_relative.json");
+    test::test_error::<Yoshida8Kernel>(&state, "analysis/yoshida8.json");
+    test::test_error::<KahanLi6RelativeKernel>(&state, "analysis/kahan_li6_relative.json");
+    test::test_error::<KahanLi6Kernel>(&state, "analysis/kahan_li6.json");
+    test::test_error::<KahanLi8RelativeKernel>(&state, "analysis/kahan_li8_relative.json");
+    test::test_error::<KahanLi8Kernel>(&state, "analysis/kahan_li8.json");
+    test::test_error::<Suzuki6RelativeKernel>(&state, "analysis/suzuki6_relative.json");
+    test::test_error::<Suzuki6Kernel>(&state, "analysis/suzuki6.json");
+    test::test_error::<Suzuki8RelativeKernel>(&state, "analysis/suzuki8_relative.json");
+    test::test_error::<Suzuki8Kernel>(&state, "analysis/suzuki8.json");
+    test::test_error::<PefrlRelativeKernel>(&state, "analysis/pefrl_relative.json");
+    test::test_error::<PefrlKernel>(&state, "analysis/pefrl.json");
+    test::test_error::<McLachlanRelativeKernel>(&state, "analysis/mclachlan_relative.json");
+    test::test_error::<McLachlanKernel>(&state, "analysis/mclachlan.json");
+    test::test_error::<BlanesMoan4RelativeKernel>(&state, "analysis/blanes_moan4_relative.json");
+    test::test_error::<BlanesMoan4Kernel>(&state, "analysis/blanes_moan4.json");
+    test::test_error::<BlanesMoan6RelativeKernel>(&state, "analysis/blanes_moan6_relative.json");
+    test::test_error::<BlanesMoan6Kernel>(&state, "analysis/blanes_moan6.json");
+    test::test_error::<VelVerletCompensatedKernel>(&state, "analysis/vel_verlet_compensated.json");
     test::test_error::<VelVerletRelativeKernel>(&state, "analysis/vel_verlet_relative.json");
     test::test_error::<VelVerletKernel>(&state, "analysis/vel_verlet.json");
+    test::test_error::<SymplecticEulerCompensatedKernel>(
+        &state,
+        "analysis/symplectic_euler_compensated.json",
+    );
     test::test_error::<SymplecticEulerRelativeKernel>(
         &state,
         "analysis/symplectic_euler_relative.json",
     );
     test::test_error::<SymplecticEulerKernel>(&state, "analysis/symplectic_euler.json");
     test::test_error::<RK4Kernel>(&state, "analysis/rk4.json");
+    test::test_error::<BulirschStoerKernel>(&state, "analysis/bulirsch_stoer.json");
+    test::test_adaptive::<DormandPrince54Kernel>(&state, "analysis/dormand_prince54.json");
+    test::test_adaptive::<BulirschStoerKernel>(&state, "analysis/bulirsch_stoer_adaptive.json");
+    test::test_adaptive::<Ias15Kernel>(&state, "analysis/ias15.json");
+    test::test_adaptive::<ChainKernel>(&state, "analysis/chain.json");
+    test::test_error_n_body(
+        &<kernels::n_body::WisdomHolmanKernel>::default(),
+        &state,
+        "analysis/wisdom_holman.json",
+    );
 }
@@ -1,9 +1,23 @@
+use std::sync::Arc;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use serde::Serialize;
 
 use crate::kernels::{
-    three_body::{ThreeBodyKernel, Yoshida4RelativeKernel},
-    PhysicsState,
+    external_acceleration,
+    n_body::{
+        self, BarnesHut, CollisionKernel, CollisionOutcome, DirectSummation, FastMultipole,
+        ForceSolver, HermiteKernel, KsRegularizedKernel, NBodyKernel, SimdDirectSummation,
+    },
+    three_body::{
+        AdaptiveThreeBodyKernel, ChainKernel, DenseOutput, Ias15Kernel, ThreeBodyKernel,
+        TimeTransformedKernel, Tolerance, VelVerletKernel, Yoshida4Kernel,
+    },
+    CloseApproach, Coulomb, Direction, Escape, ExternalPotential, ForceLaw, MiyamotoNagaiPotential,
+    Mond, NfwPotential, PhysicsState, PlaneCrossing, PlummerPotential, PointMassPotential,
+    PostNewtonian, PowerLaw, Softening, TidalField, Yukawa,
 };
+use crate::{util, Vec3, Vec3DD, Vec3f};
 
 #[derive(Serialize)]
 struct DataPoint {
@@ -14,15 +28,36 @@ struct DataPoint {
     v_std: f64,
     p_diff_max: f64,
     v_diff_max: f64,
+    energy_error: f64,
 }
 
 pub fn test_error<T: ThreeBodyKernel>(state: &PhysicsState, outfile: &str) {
-    let kernal_name = std::any::type_name::<T>();
+    test_error_with(
+        std::any::type_name::<T>(),
+        state,
+        outfile,
+        |state1, steps, dt| T::simulate(state1, 1, steps, dt),
+    );
+}
+
+pub fn test_error_n_body<T: NBodyKernel>(kernel: &T, state: &PhysicsState, outfile: &str) {
+    test_error_with(
+        std::any::type_name::<T>(),
+        state,
+        outfile,
+        |state1, steps, dt| kernel.simulate(state1, 1, steps, dt),
+    );
+}
 
+fn test_error_with<F: Fn(&mut PhysicsState, u64, u64)>(
+    kernal_name: &str,
+    state: &PhysicsState,
+    outfile: &str,
+    simulate: F,
+) {
     let max_k = 25;
     let total_time = 2u64.pow(max_k);
-    let mut ground_truth = state.clone();
-    Yoshida4RelativeKernel::simulate(&mut ground_truth, 1, total_time, 1);
+    let ground_truth = reference_solution(state, total_time);
 
     let mut data: Vec<DataPoint> = vec![];
 
@@ -35,9 +70,11 @@ pub fn test_error<T: ThreeBodyKernel>(state: &PhysicsState, outfile: &str) {
         println!("dt = {}", dt);
 
         let timer = std::time::Instant::now();
-        T::simulate(&mut state1, 1, total_time / dt, dt);
+        simulate(&mut state1, total_time / dt, dt);
         println!("time = {}ns", timer.elapsed().as_nanos());
         let (p_std, v_std, p_diff_max, v_diff_max) = state1.print_deviation(&ground_truth);
+        let energy_error = energy_error(state, &state1);
+        println!("Energy relative error: {:.5e}", energy_error);
         data.push(DataPoint {
             kernel: kernal_name.to_string(),
             dt,
@@ -46,6 +83,150 @@ pub fn test_error<T: ThreeBodyKernel>(state: &PhysicsState, outfile: &str) {
             v_std,
             p_diff_max,
             v_diff_max,
+            energy_error,
+        });
+
+        println!("--------------------------------");
+    }
+
+    let json = serde_json::to_string(&data).expect("Failed to serialize data");
+    std::fs::write(outfile, json).expect("Failed to write to file");
+}
+
+/// Relative energy error of `state1` against `state0`. With a single
+/// massive body the total energy is that body's alone, so the largest
+/// relative error in the specific orbital energies of the massless bodies
+/// around it is used instead.
+fn energy_error(state0: &PhysicsState, state1: &PhysicsState) -> f64 {
+    let massive: Vec<usize> = (0..state0.m.len()).filter(|&i| state0.m[i] > 0.0).collect();
+    if massive.len() != 1 {
+        let e0 = state0.calc_total_energy();
+        return ((state1.calc_total_energy() - e0) / e0).abs();
+    }
+    let c = massive[0];
+    let mu = util::GRAVITY_CONSTANT * state0.m[c];
+    let specific_energy = |state: &PhysicsState, i: usize| {
+        (state.v[i] - state.v[c]).norm_squared() / 2.0 - mu / (state.p[i] - state.p[c]).norm()
+    };
+    let mut max: f64 = 0.0;
+    for i in 0..state0.m.len() {
+        if i != c {
+            let e0 = specific_energy(state0, i);
+            max = max.max(((specific_energy(state1, i) - e0) / e0).abs());
+        }
+    }
+    return max;
+}
+
+/// Reference solution for the convergence tests. IAS15 stays at machine
+/// precision over the whole run, so the measured deviation is the error of the
+/// kernel under test alone.
+fn ground_truth(state: &PhysicsState, total_time: u64) -> PhysicsState {
+    let mut ground_truth = state.clone();
+    Ias15Kernel::simulate(&mut ground_truth, 1, total_time, 1);
+    return ground_truth;
+}
+
+/// Step of the double-double reference, at which the truncation error of
+/// Yoshida4 is far below the round-off of `f64` on the test orbits.
+const REFERENCE_DT: u64 = 1 << 4;
+
+/// Reference solution for the error tests. Plain Newtonian states are
+/// integrated in double-double precision by `Yoshida4Kernel<Vec3DD>`, which is
+/// beyond the round-off of any `f64` kernel, and the others by IAS15.
+fn reference_solution(state: &PhysicsState, total_time: u64) -> PhysicsState {
+    let newtonian = matches!(state.softening, Softening::None)
+        && !state.post_newtonian.is_enabled()
+        && state.external.is_empty()
+        && state.force_law.is_none();
+    if !newtonian || state.p.len() != 3 {
+        return ground_truth(state, total_time);
+    }
+    let mut reference = state.clone();
+    let dt = REFERENCE_DT.min(total_time);
+    Yoshida4Kernel::<Vec3DD>::simulate(&mut reference, 1, total_time / dt, dt);
+    if !total_time.is_multiple_of(dt) {
+        Yoshida4Kernel::<Vec3DD>::simulate(&mut reference, 1, 1, total_time % dt);
+    }
+    return reference;
+}
+
+/// Times a hand-written kernel `A` against the table driven kernel `B`
+/// implementing the same method, and prints how far their results differ.
+pub fn compare_kernels<A: ThreeBodyKernel, B: ThreeBodyKernel>(
+    state: &PhysicsState,
+    steps: u64,
+    dt: u64,
+) {
+    let mut state_a = state.clone();
+    let mut state_b = state.clone();
+
+    println!("--------------------------------");
+    let timer = std::time::Instant::now();
+    A::simulate(&mut state_a, 1, steps, dt);
+    println!("{}", std::any::type_name::<A>());
+    println!("time = {}ns", timer.elapsed().as_nanos());
+
+    let timer = std::time::Instant::now();
+    B::simulate(&mut state_b, 1, steps, dt);
+    println!("{}", std::any::type_name::<B>());
+    println!("time = {}ns", timer.elapsed().as_nanos());
+
+    state_b.print_deviation(&state_a);
+    println!("--------------------------------");
+}
+
+#[derive(Serialize)]
+struct AdaptiveDataPoint {
+    kernel: String,
+    rtol: f64,
+    total_time: u64,
+    accepted: u64,
+    rejected: u64,
+    p_std: f64,
+    v_std: f64,
+    p_diff_max: f64,
+    v_diff_max: f64,
+}
+
+pub fn test_adaptive<T: AdaptiveThreeBodyKernel>(state: &PhysicsState, outfile: &str) {
+    let kernel_name = std::any::type_name::<T>();
+
+    let total_time = 2u64.pow(25);
+    let ground_truth = ground_truth(state, total_time);
+
+    let mut data: Vec<AdaptiveDataPoint> = vec![];
+
+    for i in 2..=14 {
+        let tolerance = Tolerance {
+            atol: 1e-6,
+            rtol: 10f64.powi(-i),
+        };
+        let mut state1 = state.clone();
+
+        println!("--------------------------------");
+        println!("{}", kernel_name);
+        println!("rtol = {:e}", tolerance.rtol);
+
+        let timer = std::time::Instant::now();
+        let stats = T::simulate_adaptive(&mut state1, total_time, tolerance);
+        println!("time = {}ns", timer.elapsed().as_nanos());
+        println!(
+            "accepted = {}, rejected = {}",
+            stats.accepted, stats.rejected
+        );
+        state1.print_errors(state);
+        let (p_std, v_std, p_diff_max, v_diff_max) = state1.print_deviation(&ground_truth);
+        data.push(AdaptiveDataPoint {
+            kernel: kernel_name.to_string(),
+            rtol: tolerance.rtol,
+            total_time,
+            accepted: stats.accepted,
+            rejected: stats.rejected,
+            p_std,
+            v_std,
+            p_diff_max,
+            v_diff_max,
         });
 
         println!("--------------------------------");
@@ -54,3 +235,1064 @@ pub fn test_error<T: ThreeBodyKernel>(state: &PhysicsState, outfile: &str) {
     let json = serde_json::to_string(&data).expect("Failed to serialize data");
     std::fs::write(outfile, json).expect("Failed to write to file");
 }
+
+/// Samples a Plummer sphere of `n` solar-mass stars with a 1 pc scale radius
+/// (Aarseth, Hénon & Wielen 1974), normalized to the center of mass frame.
+pub fn plummer_sphere(n: usize, seed: u64) -> PhysicsState {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let total_mass = n as f64 * util::MASS_SUN;
+    let a = util::PARSEC;
+    let v_scale = (util::GRAVITY_CONSTANT * total_mass / a).sqrt();
+
+    let isotropic = |rng: &mut StdRng, len: f64| {
+        let z: f64 = rng.random_range(-1.0..1.0);
+        let phi: f64 = rng.random_range(0.0..std::f64::consts::TAU);
+        let s = (1.0 - z * z).sqrt();
+        Vec3::new(len * s * phi.cos(), len * s * phi.sin(), len * z)
+    };
+
+    let mut p = Vec::with_capacity(n);
+    let mut v = Vec::with_capacity(n);
+    for _ in 0..n {
+        // Cut off the tail so a single star cannot blow up the bounding box.
+        let x: f64 = rng.random_range(1e-3..0.999);
+        let r = 1.0 / (x.powf(-2.0 / 3.0) - 1.0).sqrt();
+        p.push(isotropic(&mut rng, r * a));
+
+        let q = loop {
+            let q: f64 = rng.random();
+            let g: f64 = rng.random_range(0.0..0.1);
+            if g < q * q * (1.0 - q * q).powf(3.5) {
+                break q;
+            }
+        };
+        let v_esc = std::f64::consts::SQRT_2 * (1.0 + r * r).powf(-0.25);
+        v.push(isotropic(&mut rng, q * v_esc * v_scale));
+    }
+
+    let mut state = PhysicsState {
+        p,
+        v,
+        m: vec![util::MASS_SUN; n],
+        r: vec![0.0; n],
+        n_test: 0,
+        t: 0,
+        t_frac: 0.0,
+        softening: Softening::None,
+        post_newtonian: PostNewtonian::default(),
+        external: vec![],
+        force_law: None,
+        events: vec![],
+    };
+    state.normalize();
+    return state;
+}
+
+/// Compares the accelerations from `solver` against direct summation and
+/// returns the (rms, max) relative error over all bodies.
+pub fn compare_accelerations<F: ForceSolver>(solver: &F, state: &PhysicsState) -> (f64, f64) {
+    let n = state.p.len();
+    let m: Vec<f64> = state.m.iter().map(|m| m * util::GRAVITY_CONSTANT).collect();
+
+    let mut a_direct = vec![Vec3::ZERO; n];
+    let timer = std::time::Instant::now();
+    n_body::calc_a(&state.p, &m, &state.softening, &mut a_direct);
+    println!("direct time = {}ns", timer.elapsed().as_nanos());
+
+    let mut a = vec![Vec3::ZERO; n];
+    let timer = std::time::Instant::now();
+    solver.calc_a(&state.p, &m, &state.softening, &mut a);
+    println!(
+        "{} time = {}ns",
+        std::any::type_name::<F>(),
+        timer.elapsed().as_nanos()
+    );
+
+    let mut rms: f64 = 0.0;
+    let mut max: f64 = 0.0;
+    for i in 0..n {
+        let err = (a[i] - a_direct[i]).norm() / a_direct[i].norm();
+        rms += err * err;
+        max = max.max(err);
+    }
+    rms = (rms / n as f64).sqrt();
+    println!("Acceleration rms relative error: {:.5e}", rms);
+    println!("Acceleration max relative error: {:.5e}", max);
+    return (rms, max);
+}
+
+pub fn test_barnes_hut(state: &PhysicsState, thetas: &[f64]) {
+    for &theta in thetas {
+        println!("--------------------------------");
+        println!("theta = {}", theta);
+        compare_accelerations(&BarnesHut::new(theta), state);
+        println!("--------------------------------");
+    }
+}
+
+pub fn test_fmm(state: &PhysicsState, orders: &[usize], theta: f64) {
+    for &order in orders {
+        println!("--------------------------------");
+        println!("order = {}, theta = {}", order, theta);
+        compare_accelerations(&FastMultipole::new(order, theta), state);
+        println!("--------------------------------");
+    }
+}
+
+/// Reports the throughput of the scalar and SoA direct summation, counting
+/// each ordered pair of bodies as one interaction.
+pub fn test_simd_direct(state: &PhysicsState, threads: &[usize], repeat: u32) {
+    let n = state.p.len();
+    let m: Vec<f64> = state.m.iter().map(|m| m * util::GRAVITY_CONSTANT).collect();
+    let interactions = (n * (n - 1)) as f64 * repeat as f64;
+    let mut a = vec![Vec3::ZERO; n];
+
+    let timer = std::time::Instant::now();
+    for _ in 0..repeat {
+        n_body::calc_a(&state.p, &m, &state.softening, &mut a);
+    }
+    let elapsed = timer.elapsed().as_secs_f64();
+    println!("--------------------------------");
+    println!("scalar direct");
+    println!("interactions/s = {:.5e}", interactions / elapsed);
+
+    for &t in threads {
+        let solver = SimdDirectSummation::new(t);
+        println!("--------------------------------");
+        println!("SoA direct, threads = {}", t);
+        let timer = std::time::Instant::now();
+        for _ in 0..repeat {
+            solver.calc_a(&state.p, &m, &state.softening, &mut a);
+        }
+        let elapsed = timer.elapsed().as_secs_f64();
+        println!("interactions/s = {:.5e}", interactions / elapsed);
+        compare_accelerations(&solver, state);
+    }
+    println!("--------------------------------");
+}
+
+/// Compares the KS regularized kernel with plain Velocity Verlet against the
+/// IAS15 ground truth, and lists the pairs entering and leaving
+/// regularization with the energy error at each transition.
+pub fn test_ks_regularization(state: &PhysicsState, r_on: f64, dts: &[u64], total_time: u64) {
+    let ground_truth = ground_truth(state, total_time);
+    let e0 = state.calc_total_energy();
+    for &dt in dts {
+        println!("--------------------------------");
+        println!("dt = {}", dt);
+        let mut state1 = state.clone();
+        <n_body::VelVerletKernel>::default().simulate(&mut state1, 1, total_time / dt, dt);
+        println!("Velocity Verlet");
+        state1.print_deviation(&ground_truth);
+        state1.print_errors(state);
+
+        let kernel = KsRegularizedKernel::new(r_on);
+        let (state2, log) = kernel.kernel_logged(state.clone(), total_time / dt, dt);
+        println!("KS regularized");
+        for transition in &log {
+            println!(
+                "t = {}, pair = {:?}, {}, energy error: {:.10e}, relative: {:.5e}",
+                transition.t,
+                transition.pair,
+                if transition.regularized { "on" } else { "off" },
+                transition.energy_error,
+                transition.energy_error / e0.abs()
+            );
+        }
+        state2.print_deviation(&ground_truth);
+        state2.print_errors(state);
+        println!("--------------------------------");
+    }
+}
+
+/// Gives every body of `state` the radius `radius` and runs it with velocity
+/// Verlet, merging and then bouncing the bodies that collide, reporting the
+/// collision log and the conservation errors.
+pub fn test_collisions(state: &PhysicsState, radius: f64, dt: u64, total_time: u64) {
+    let mut state = state.clone();
+    state.r = vec![radius; state.p.len()];
+    for outcome in [
+        CollisionOutcome::Merge,
+        CollisionOutcome::Bounce { restitution: 1.0 },
+    ] {
+        println!("--------------------------------");
+        println!("{:?}", outcome);
+        let kernel = CollisionKernel::new(<n_body::VelVerletKernel>::default(), outcome);
+        let timer = std::time::Instant::now();
+        let (state1, log) = kernel.kernel_logged(state.clone(), total_time / dt, dt);
+        println!("time = {}ns", timer.elapsed().as_nanos());
+        for collision in &log {
+            println!(
+                "t = {}, pair = {:?}, speed = {:.5e}",
+                collision.t, collision.pair, collision.speed
+            );
+        }
+        println!(
+            "collisions = {}, bodies = {} -> {}",
+            log.len(),
+            state.p.len(),
+            state1.p.len()
+        );
+        println!(
+            "Mass relative error: {:.5e}",
+            (state1.total_mass() - state.total_mass()) / state.total_mass()
+        );
+        state1.print_errors(&state);
+        println!("--------------------------------");
+    }
+}
+
+/// Runs the block step Hermite kernel at each `eta` with top level step `dt`,
+/// and Velocity Verlet with as many force evaluations per body, reporting the
+/// conservation errors.
+pub fn test_hermite(state: &PhysicsState, etas: &[f64], dt: u64, total_time: u64) {
+    let n = state.p.len() as u64;
+    for &eta in etas {
+        println!("--------------------------------");
+        println!("Hermite, eta = {}", eta);
+        let kernel = HermiteKernel::new(eta);
+        let timer = std::time::Instant::now();
+        let (state1, stats) = kernel.kernel_with_stats(state.clone(), total_time / dt, dt);
+        println!("time = {}ns", timer.elapsed().as_nanos());
+        println!(
+            "blocks = {}, body steps = {}",
+            stats.blocks, stats.body_steps
+        );
+        state1.print_errors(state);
+
+        let steps = (stats.body_steps / n).max(1);
+        let mut state2 = state.clone();
+        println!("Velocity Verlet, steps = {}", steps);
+        let timer = std::time::Instant::now();
+        <n_body::VelVerletKernel>::default().simulate(&mut state2, 1, steps, total_time / steps);
+        println!("time = {}ns", timer.elapsed().as_nanos());
+        state2.print_errors(state);
+        println!("--------------------------------");
+    }
+}
+
+/// Runs `state` without softening and with Plummer and spline softening of
+/// length `eps`. For each, checks the softened accelerations of the SIMD and
+/// Barnes–Hut solvers against direct summation, then reports the energy error
+/// of velocity Verlet at each `dt` and of the Hermite kernel, against the
+/// matching softened potential.
+pub fn test_softening(state: &PhysicsState, eps: f64, dts: &[u64], total_time: u64) {
+    for softening in [
+        Softening::None,
+        Softening::Plummer(eps),
+        Softening::Spline(eps),
+    ] {
+        let mut state = state.clone();
+        state.softening = softening;
+        println!("--------------------------------");
+        println!("{:?}", softening);
+        compare_accelerations(&SimdDirectSummation::new(1), &state);
+        compare_accelerations(&BarnesHut::new(0.5), &state);
+        compare_accelerations(&FastMultipole::default(), &state);
+        for &dt in dts {
+            let mut state1 = state.clone();
+            println!("Velocity Verlet, dt = {}", dt);
+            <n_body::VelVerletKernel>::default().simulate(&mut state1, 1, total_time / dt, dt);
+            state1.print_errors(&state);
+        }
+        println!("Hermite, eta = 0.02");
+        let state1 = HermiteKernel::default().kernel(state.clone(), 1, total_time);
+        state1.print_errors(&state);
+        println!("--------------------------------");
+    }
+}
+
+/// Burrau's Pythagorean problem: masses 3, 4 and 5 at rest on the corners of a
+/// 3-4-5 right triangle, opposite the sides of matching length. Lengths are in
+/// units of `length` and masses in units of `mass`; also returns the time
+/// unit `sqrt(length^3 / (G mass))` in seconds. The system goes through a
+/// series of near-collisions until, around t = 60, the two heavier bodies
+/// leave as a binary in one direction and the lightest in the other.
+pub fn pythagorean_problem(length: f64, mass: f64) -> (PhysicsState, f64) {
+    let mut state = PhysicsState {
+        p: vec![
+            Vec3::new(1.0, 3.0, 0.0) * length,
+            Vec3::new(-2.0, -1.0, 0.0) * length,
+            Vec3::new(1.0, -1.0, 0.0) * length,
+        ],
+        v: vec![Vec3::ZERO; 3],
+        m: vec![3.0 * mass, 4.0 * mass, 5.0 * mass],
+        r: vec![0.0; 3],
+        n_test: 0,
+        t: 0,
+        t_frac: 0.0,
+        softening: Softening::None,
+        post_newtonian: PostNewtonian::default(),
+        external: vec![],
+        force_law: None,
+        events: vec![],
+    };
+    state.normalize();
+    let time_unit = (length * length * length / (util::GRAVITY_CONSTANT * mass)).sqrt();
+    return (state, time_unit);
+}
+
+/// Runs the Pythagorean problem to t = 70 with the chain regularized kernel at
+/// each `rtol`, against IAS15.
+pub fn test_pythagorean(rtols: &[f64]) {
+    let (state, time_unit) = pythagorean_problem(util::AU, util::MASS_SUN);
+    let total_time = (70.0 * time_unit / util::UNIT_TIME) as u64;
+    let e0 = state.calc_total_energy();
+    let reference = ground_truth(&state, total_time);
+    let separations = |state: &PhysicsState| {
+        [(0, 1), (1, 2), (2, 0)].map(|(i, j)| (state.p[i] - state.p[j]).norm() / util::AU)
+    };
+    println!("--------------------------------");
+    println!("IAS15");
+    println!(
+        "Energy relative error: {:.5e}",
+        (reference.calc_total_energy() - e0) / e0.abs()
+    );
+    println!("Separations: {:.5?}", separations(&reference));
+    println!("--------------------------------");
+
+    for &rtol in rtols {
+        let tolerance = Tolerance {
+            atol: 0.0,
+            rtol: rtol,
+        };
+        let mut state1 = state.clone();
+        println!("--------------------------------");
+        println!("chain, rtol = {:e}", rtol);
+        let timer = std::time::Instant::now();
+        let stats = ChainKernel::simulate_adaptive(&mut state1, total_time, tolerance);
+        println!("time = {}ns", timer.elapsed().as_nanos());
+        println!(
+            "accepted = {}, rejected = {}",
+            stats.accepted, stats.rejected
+        );
+        println!(
+            "Energy relative error: {:.5e}",
+            (state1.calc_total_energy() - e0) / e0.abs()
+        );
+        println!("Separations: {:.5?}", separations(&state1));
+        state1.print_deviation(&reference);
+        println!("--------------------------------");
+    }
+}
+
+/// Bulge, disk and halo of a Milky Way like galaxy, after the Allen &
+/// Santillan (1991) bulge and disk with an NFW halo.
+pub fn galaxy() -> Vec<Arc<dyn ExternalPotential>> {
+    let kpc = 1e3 * util::PARSEC;
+    return vec![
+        Arc::new(PlummerPotential {
+            m: 1.406e10 * util::MASS_SUN,
+            b: 0.3873 * kpc,
+        }),
+        Arc::new(MiyamotoNagaiPotential {
+            m: 8.561e10 * util::MASS_SUN,
+            a: 5.3178 * kpc,
+            b: 0.25 * kpc,
+        }),
+        Arc::new(NfwPotential {
+            m: 4.4e11 * util::MASS_SUN,
+            r_s: 16.0 * kpc,
+        }),
+    ];
+}
+
+/// Checks each external potential's acceleration against the central
+/// difference of its potential, then puts `state` on a circular orbit at
+/// 8 kpc in `galaxy` and runs it for `total_time` with Yoshida4, reporting
+/// the conservation errors and the galactocentric radius of its center of
+/// mass.
+pub fn test_external_potentials(state: &PhysicsState, dt: u64, total_time: u64) {
+    let kpc = 1e3 * util::PARSEC;
+    let mut potentials = galaxy();
+    potentials.push(Arc::new(PointMassPotential {
+        m: 4e6 * util::MASS_SUN,
+    }));
+    potentials.push(Arc::new(TidalField::point_mass(
+        1e12 * util::MASS_SUN,
+        50.0 * kpc,
+    )));
+    let mut rng = StdRng::seed_from_u64(0);
+    println!("--------------------------------");
+    for potential in &potentials {
+        let mut max: f64 = 0.0;
+        for _ in 0..100 {
+            let p = Vec3::new(
+                rng.random_range(-20.0..20.0) * kpc,
+                rng.random_range(-20.0..20.0) * kpc,
+                rng.random_range(-2.0..2.0) * kpc,
+            );
+            let h = 1e-5 * p.norm();
+            let mut grad = [0.0; 3];
+            for k in 0..3 {
+                let mut e = [0.0; 3];
+                e[k] = h;
+                let e = Vec3::new(e[0], e[1], e[2]);
+                grad[k] =
+                    (potential.potential(&(p + e)) - potential.potential(&(p - e))) / (2.0 * h);
+            }
+            let a = potential.acceleration(&p);
+            let err = (a + Vec3::new(grad[0], grad[1], grad[2])).norm() / a.norm();
+            max = max.max(err);
+        }
+        println!("{:?}", potential);
+        println!("Acceleration max relative error: {:.5e}", max);
+    }
+    println!("--------------------------------");
+
+    let external = galaxy();
+    let r0 = Vec3::new(8.0 * kpc, 0.0, 0.0);
+    let v_c = (8.0 * kpc * external_acceleration(&external, &r0).norm()).sqrt();
+    println!("v_c = {:.5e}m/s", v_c);
+    let mut state0 = state.clone();
+    for i in 0..state0.p.len() {
+        state0.p[i] += r0;
+        state0.v[i] += Vec3::new(0.0, v_c, 0.0);
+    }
+    state0.external = external;
+    let mut state1 = state0.clone();
+    let timer = std::time::Instant::now();
+    <n_body::Yoshida4Kernel>::default().simulate(&mut state1, 1, total_time / dt, dt);
+    println!("time = {}ns", timer.elapsed().as_nanos());
+    state1.print_errors(&state0);
+    println!(
+        "Center of mass radius: {:.5} kpc",
+        state1.calc_center_of_mass().norm() / kpc
+    );
+    println!("--------------------------------");
+}
+
+/// Binary of masses `m` on an orbit with semi-major axis `a` and eccentricity
+/// `e` in the xy plane, starting at pericenter, plus a massless third body on
+/// a circular orbit 1000 times wider, normalized to the center of mass frame.
+/// Also returns the orbital period in seconds.
+pub fn binary(m: [f64; 2], a: f64, e: f64, post_newtonian: PostNewtonian) -> (PhysicsState, f64) {
+    let mu = util::GRAVITY_CONSTANT * (m[0] + m[1]);
+    let q = a * (1.0 - e);
+    let v_q = (mu * (1.0 + e) / q).sqrt();
+    let mut state = PhysicsState {
+        p: vec![
+            Vec3::new(-q * m[1] / (m[0] + m[1]), 0.0, 0.0),
+            Vec3::new(q * m[0] / (m[0] + m[1]), 0.0, 0.0),
+            Vec3::new(0.0, 1000.0 * a, 0.0),
+        ],
+        v: vec![
+            Vec3::new(0.0, -v_q * m[1] / (m[0] + m[1]), 0.0),
+            Vec3::new(0.0, v_q * m[0] / (m[0] + m[1]), 0.0),
+            Vec3::new(-(mu / (1000.0 * a)).sqrt(), 0.0, 0.0),
+        ],
+        m: vec![m[0], m[1], 0.0],
+        r: vec![0.0; 3],
+        n_test: 0,
+        t: 0,
+        t_frac: 0.0,
+        softening: Softening::None,
+        post_newtonian: post_newtonian,
+        external: vec![],
+        force_law: None,
+        events: vec![],
+    };
+    state.normalize();
+    let period = std::f64::consts::TAU * (a * a * a / mu).sqrt();
+    return (state, period);
+}
+
+/// Argument of pericenter of the binary made by the first two bodies of
+/// `state`, from the direction of the Laplace–Runge–Lenz vector in the xy
+/// plane, and the semi-major axis from its Newtonian energy.
+fn binary_elements(state: &PhysicsState) -> (f64, f64) {
+    let mu = util::GRAVITY_CONSTANT * (state.m[0] + state.m[1]);
+    let [x, y, _] = <[f64; 3]>::from(state.p[1] - state.p[0]);
+    let [vx, vy, _] = <[f64; 3]>::from(state.v[1] - state.v[0]);
+    let r = (x * x + y * y).sqrt();
+    let h = x * vy - y * vx;
+    let omega = (-vx * h - mu * y / r).atan2(vy * h - mu * x / r);
+    let energy = (vx * vx + vy * vy) / 2.0 - mu / r;
+    return (omega, -mu / (2.0 * energy));
+}
+
+/// Integrates Mercury around the Sun for about `centuries` with IAS15, with
+/// and without the 1PN corrections, and compares the advance of the
+/// perihelion with `6 pi G M / (c^2 a (1 - e^2))` per orbit, about 43 arcsec
+/// per century. The run ends after a whole number of orbits, back at
+/// perihelion, where the periodic 1PN terms of the osculating elements are
+/// the same as at the start.
+pub fn test_perihelion_precession(centuries: u64) {
+    let (a, e) = (0.387098 * util::AU, 0.20563);
+    let m = [util::MASS_SUN, 3.3011e23];
+    let century = 100.0 * util::YEAR;
+    let arcsec = std::f64::consts::PI / (180.0 * 3600.0);
+    for pn1 in [false, true] {
+        let post_newtonian = PostNewtonian {
+            pn1: pn1,
+            pn2_5: false,
+        };
+        let (mut state, period) = binary(m, a, e, post_newtonian);
+        let duration = (centuries as f64 * century / period).round() * period;
+        let mu = util::GRAVITY_CONSTANT * (m[0] + m[1]);
+        let expected = if pn1 {
+            6.0 * std::f64::consts::PI * mu
+                / (util::SPEED_LIGHT * util::SPEED_LIGHT * a * (1.0 - e * e))
+                * duration
+                / period
+        } else {
+            0.0
+        };
+
+        println!("--------------------------------");
+        println!("{:?}", post_newtonian);
+        let (omega0, _) = binary_elements(&state);
+        let timer = std::time::Instant::now();
+        let stats = Ias15Kernel::simulate_adaptive(
+            &mut state,
+            (duration / util::UNIT_TIME) as u64,
+            Tolerance::default(),
+        );
+        println!("time = {}ns", timer.elapsed().as_nanos());
+        println!(
+            "accepted = {}, rejected = {}",
+            stats.accepted, stats.rejected
+        );
+        let (omega1, _) = binary_elements(&state);
+        let advance = omega1 - omega0;
+        println!(
+            "Perihelion advance: {:.5} arcsec/century, expected {:.5}",
+            advance / arcsec * century / duration,
+            expected / arcsec * century / duration
+        );
+        println!("Difference: {:.5e} arcsec", (advance - expected) / arcsec);
+        println!("--------------------------------");
+    }
+}
+
+/// Integrates a circular binary of two 1.4 solar mass neutron stars starting
+/// at separation `a0` with the 2.5PN radiation reaction, until the
+/// separation should have halved, and compares the semi-major axis with
+/// Peters' (1964) `a0 (1 - t / tau)^(1/4)`, where
+/// `tau = 5 c^5 a0^4 / (256 G^3 m1 m2 (m1 + m2))`.
+pub fn test_inspiral(a0: f64) {
+    let m = [1.4 * util::MASS_SUN; 2];
+    let post_newtonian = PostNewtonian {
+        pn1: false,
+        pn2_5: true,
+    };
+    let (mut state, period) = binary(m, a0, 0.0, post_newtonian);
+    let mu = m.map(|m| m * util::GRAVITY_CONSTANT);
+    let c5 = util::SPEED_LIGHT.powi(5);
+    let tau = 5.0 * c5 * a0.powi(4) / (256.0 * mu[0] * mu[1] * (mu[0] + mu[1]));
+    println!("--------------------------------");
+    println!("period = {:.5e}s, tau = {:.5e}s", period, tau);
+    let chunk = (tau * (1.0 - 1.0 / 16.0) / 4.0 / util::UNIT_TIME) as u64;
+    let timer = std::time::Instant::now();
+    for _ in 0..4 {
+        let stats = Ias15Kernel::simulate_adaptive(&mut state, chunk, Tolerance::default());
+        let t = state.time();
+        let (_, a) = binary_elements(&state);
+        let expected = a0 * (1.0 - t / tau).powf(0.25);
+        println!(
+            "t = {:.5e}s, steps = {}, a / a0 = {:.8}, expected {:.8}, relative error {:.5e}",
+            t,
+            stats.accepted,
+            a / a0,
+            expected / a0,
+            (a - expected) / expected
+        );
+    }
+    println!("time = {}ns", timer.elapsed().as_nanos());
+    println!("--------------------------------");
+}
+
+/// Checks each force law against its potential by finite differences, then
+/// runs a wide eccentric binary under it with the three-body and N-body
+/// Yoshida4 kernels, which should agree, and prints their energy errors. The
+/// charges of the Coulomb binary are picked to mimic gravity, so it must
+/// follow the Newtonian orbit.
+pub fn test_force_laws(steps_per_orbit: u64, orbits: u64) {
+    let m = [util::MASS_SUN, 0.5 * util::MASS_SUN];
+    let a = 1e4 * util::AU;
+    let (state, period) = binary(m, a, 0.5, PostNewtonian::default());
+    let mu: Vec<f64> = state.m.iter().map(|m| m * util::GRAVITY_CONSTANT).collect();
+    let q = (util::GRAVITY_CONSTANT / util::COULOMB_CONSTANT).sqrt();
+    let laws: Vec<Arc<dyn ForceLaw>> = vec![
+        Arc::new(Coulomb {
+            q: vec![q * m[0], -q * m[1], 0.0],
+        }),
+        Arc::new(Yukawa { lambda: 10.0 * a }),
+        Arc::new(PowerLaw { n: 1.0, r0: a }),
+        Arc::new(PowerLaw { n: 2.5, r0: a }),
+        Arc::new(Mond { a0: Mond::A0 }),
+    ];
+    let dt = (period / util::UNIT_TIME) as u64 / steps_per_orbit;
+    let steps = steps_per_orbit * orbits;
+
+    let mut newtonian = state.clone();
+    <n_body::Yoshida4Kernel>::default().simulate(&mut newtonian, 1, steps, dt);
+
+    println!("--------------------------------");
+    for law in &laws {
+        println!("{:?}", law);
+        // The attractive force on body 0 against the slope of the potential.
+        let mut max: f64 = 0.0;
+        let mut max_reaction: f64 = 0.0;
+        for k in 0..=40 {
+            let r = a * 10f64.powf(k as f64 / 10.0 - 2.0);
+            let h = 1e-5 * r;
+            let (f0, f1) = law.acceleration(&mu, 0, 1, r * r);
+            let force = f0 * mu[0] * r / util::GRAVITY_CONSTANT;
+            let slope = (law.potential(&mu, 0, 1, (r + h) * (r + h))
+                - law.potential(&mu, 0, 1, (r - h) * (r - h)))
+                / (2.0 * h);
+            max = max.max(((force - slope) / force).abs());
+            max_reaction = max_reaction.max(((f0 * mu[0] - f1 * mu[1]) / (f0 * mu[0])).abs());
+        }
+        println!("Force max relative error: {:.5e}", max);
+        println!("Reaction max relative error: {:.5e}", max_reaction);
+
+        let mut state0 = state.clone();
+        state0.force_law = Some(law.clone());
+        let mut state1 = state0.clone();
+        let timer = std::time::Instant::now();
+        <Yoshida4Kernel>::simulate(&mut state1, 1, steps, dt);
+        println!(
+            "Three-body Yoshida4, time = {}ns",
+            timer.elapsed().as_nanos()
+        );
+        state1.print_errors(&state0);
+        let mut state2 = state0.clone();
+        <n_body::Yoshida4Kernel>::default().simulate(&mut state2, 1, steps, dt);
+        println!("N-body Yoshida4");
+        state2.print_errors(&state0);
+        println!(
+            "Separation {:.5} a, N-body difference {:.5e} a, Newtonian difference {:.5e} a",
+            (state1.p[1] - state1.p[0]).norm() / a,
+            (state2.p[1] - state1.p[1]).norm() / a,
+            (newtonian.p[1] - state1.p[1]).norm() / a
+        );
+        println!("--------------------------------");
+    }
+
+    // Circular speed of a test particle around the heavier star, which
+    // flattens out at (G m a0)^(1/4) far beyond sqrt(G m / a0).
+    let mond = Mond { a0: Mond::A0 };
+    let mu = [mu[0], 0.0];
+    let r_m = (mu[0] / Mond::A0).sqrt();
+    println!("MOND radius = {:.5e} AU", r_m / util::AU);
+    for k in 0..6 {
+        let r = r_m * 10f64.powi(k - 2);
+        let (f, _) = mond.acceleration(&mu, 1, 0, r * r);
+        println!(
+            "r = {:.5e} AU, v_c = {:.5e} m/s, Newtonian {:.5e} m/s",
+            r / util::AU,
+            r * f.sqrt(),
+            (mu[0] / r).sqrt()
+        );
+    }
+    println!(
+        "Flat rotation speed {:.5e} m/s",
+        (mu[0] * Mond::A0).powf(0.25)
+    );
+    println!("--------------------------------");
+}
+
+/// The Sun, Jupiter and Saturn on circular orbits, with `n` asteroids as
+/// test particles on nearly circular orbits between 2.2 and 3.3 AU.
+pub fn asteroid_belt(n: usize, seed: u64) -> PhysicsState {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mu = util::GRAVITY_CONSTANT * util::MASS_SUN;
+    let orbit = |a: f64, phase: f64, inclination: f64| {
+        let v = (mu / a).sqrt();
+        (
+            Vec3::new(a * phase.cos(), a * phase.sin(), 0.0),
+            Vec3::new(
+                -v * phase.sin(),
+                v * phase.cos() * inclination.cos(),
+                v * phase.cos() * inclination.sin(),
+            ),
+        )
+    };
+    let (p1, v1) = orbit(5.2 * util::AU, 0.0, 0.0);
+    let (p2, v2) = orbit(9.58 * util::AU, 2.0, 0.0);
+    let mut state = PhysicsState {
+        p: vec![Vec3::ZERO, p1, p2],
+        v: vec![Vec3::ZERO, v1, v2],
+        m: vec![
+            util::MASS_SUN,
+            9.55e-4 * util::MASS_SUN,
+            2.86e-4 * util::MASS_SUN,
+        ],
+        r: vec![0.0; 3],
+        n_test: 0,
+        t: 0,
+        t_frac: 0.0,
+        softening: Softening::None,
+        post_newtonian: PostNewtonian::default(),
+        external: vec![],
+        force_law: None,
+        events: vec![],
+    };
+    for _ in 0..n {
+        let (p, v) = orbit(
+            rng.random_range(2.2..3.3) * util::AU,
+            rng.random_range(0.0..std::f64::consts::TAU),
+            rng.random_range(-0.1..0.1),
+        );
+        state.push_test_particle(p, v);
+    }
+    state.normalize();
+    return state;
+}
+
+/// Runs `state` with its test particles, and again with them as ordinary
+/// massless bodies summed over all pairs, which should give the same orbits
+/// at a fraction of the cost.
+pub fn test_test_particles(state: &PhysicsState, dt: u64, total_time: u64) {
+    let mut massless = state.clone();
+    massless.n_test = 0;
+    let run = |name: &str, kernel: &dyn Fn(&mut PhysicsState)| {
+        println!("{}, {} massive bodies", name, state.n_massive());
+        let mut state1 = state.clone();
+        let timer = std::time::Instant::now();
+        kernel(&mut state1);
+        println!("time = {}ns", timer.elapsed().as_nanos());
+        state1.print_errors(state);
+        let mut state2 = massless.clone();
+        let timer = std::time::Instant::now();
+        kernel(&mut state2);
+        println!("Massless bodies, time = {}ns", timer.elapsed().as_nanos());
+        state1.print_deviation(&state2);
+        println!("--------------------------------");
+    };
+    let steps = total_time / dt;
+    println!("--------------------------------");
+    run("Wisdom-Holman", &|state| {
+        <n_body::WisdomHolmanKernel>::default().simulate(state, 1, steps, dt)
+    });
+    run("Wisdom-Holman, SIMD", &|state| {
+        n_body::WisdomHolmanKernel::new(SimdDirectSummation::default())
+            .simulate(state, 1, steps, dt)
+    });
+    run("Yoshida4", &|state| {
+        <n_body::Yoshida4Kernel>::default().simulate(state, 1, steps, dt)
+    });
+}
+
+/// Two test particles around the Sun on orbits with semi-major axis 1 AU and
+/// eccentricities `e`, in perpendicular planes, starting at the end of the
+/// minor axis, where the distance is the semi-major axis. Also returns the
+/// orbital period in seconds.
+pub fn eccentric_orbits(e: [f64; 2]) -> (PhysicsState, f64) {
+    let mu = util::GRAVITY_CONSTANT * util::MASS_SUN;
+    let a = util::AU;
+    let n = (mu / (a * a * a)).sqrt();
+    let b = e.map(|e| a * (1.0 - e * e).sqrt());
+    let state = PhysicsState {
+        p: vec![
+            Vec3::ZERO,
+            Vec3::new(-a * e[0], b[0], 0.0),
+            Vec3::new(-a * e[1], 0.0, b[1]),
+        ],
+        v: vec![
+            Vec3::ZERO,
+            Vec3::new(-a * n, 0.0, 0.0),
+            Vec3::new(-a * n, 0.0, 0.0),
+        ],
+        m: vec![util::MASS_SUN, 0.0, 0.0],
+        r: vec![0.0; 3],
+        n_test: 0,
+        t: 0,
+        t_frac: 0.0,
+        softening: Softening::None,
+        post_newtonian: PostNewtonian::default(),
+        external: vec![],
+        force_law: None,
+        events: vec![],
+    };
+    return (state, 2.0 * std::f64::consts::PI / n);
+}
+
+/// Compares Velocity Verlet with the time transformed leapfrog on the orbits
+/// of `eccentric_orbits`, taking the same number of steps per orbit with
+/// each, against the exact Kepler solution at the time each has reached.
+pub fn test_time_transformed(e: [f64; 2], steps_per_orbit: &[u64], orbits: u64) {
+    let (state, period) = eccentric_orbits(e);
+    let mu = util::GRAVITY_CONSTANT * util::MASS_SUN;
+    let exact = |time: f64| {
+        let mut exact = state.clone();
+        for i in 1..3 {
+            n_body::kepler_drift(&mut exact.p[i], &mut exact.v[i], mu, time);
+        }
+        return exact;
+    };
+    let energy_error = |state1: &PhysicsState| {
+        let mut max: f64 = 0.0;
+        for i in 1..3 {
+            let e0 = state.v[i].norm_squared() / 2.0 - mu / state.p[i].norm();
+            let e1 = state1.v[i].norm_squared() / 2.0 - mu / state1.p[i].norm();
+            max = max.max(((e1 - e0) / e0).abs());
+        }
+        return max;
+    };
+
+    for &n in steps_per_orbit {
+        let dt = (period / n as f64 / util::UNIT_TIME) as u64;
+        let steps = n * orbits;
+        println!("--------------------------------");
+        println!("steps per orbit = {}, dt = {}", n, dt);
+
+        let mut state1 = state.clone();
+        <VelVerletKernel>::simulate(&mut state1, 1, steps, dt);
+        println!("Velocity Verlet");
+        println!("orbits = {:.5}", state1.time() / period);
+        println!("Energy relative error: {:.5e}", energy_error(&state1));
+        state1.print_deviation(&exact(state1.time()));
+
+        let mut state2 = state.clone();
+        TimeTransformedKernel::simulate(&mut state2, 1, steps, dt);
+        println!("time transformed");
+        println!("orbits = {:.5}", state2.time() / period);
+        println!("Energy relative error: {:.5e}", energy_error(&state2));
+        state2.print_deviation(&exact(state2.time()));
+        println!("--------------------------------");
+    }
+}
+
+/// Runs `T` with dense output and compares the interpolated positions and
+/// velocities at `samples` random times inside the run with IAS15, next to
+/// the deviation at the end of the run, which is at a step boundary.
+pub fn test_dense_output<T: ThreeBodyKernel>(
+    state: &PhysicsState,
+    steps: u64,
+    dt: u64,
+    samples: usize,
+) {
+    println!("--------------------------------");
+    println!("{}", std::any::type_name::<T>());
+    println!("steps = {}, dt = {}", steps, dt);
+    let mut state1 = state.clone();
+    let timer = std::time::Instant::now();
+    let dense = T::simulate_dense(&mut state1, steps, dt);
+    println!("time = {}ns", timer.elapsed().as_nanos());
+    println!("segments = {}", dense.len());
+    println!("End of run");
+    state1.print_deviation(&ground_truth(state, state1.t - state.t));
+    print_dense_deviation(state, &state1, &dense, samples);
+}
+
+/// Runs `T` with adaptive steps and dense output at `tolerance` for
+/// `duration` ticks, and compares the interpolated positions and velocities
+/// as `test_dense_output` does.
+pub fn test_adaptive_dense_output<T: AdaptiveThreeBodyKernel>(
+    state: &PhysicsState,
+    duration: u64,
+    tolerance: Tolerance,
+    samples: usize,
+) {
+    println!("--------------------------------");
+    println!("{}", std::any::type_name::<T>());
+    println!("rtol = {:e}", tolerance.rtol);
+    let mut state1 = state.clone();
+    let timer = std::time::Instant::now();
+    let (stats, dense) = T::simulate_adaptive_dense(&mut state1, duration, tolerance);
+    println!("time = {}ns", timer.elapsed().as_nanos());
+    println!(
+        "accepted = {}, rejected = {}, segments = {}",
+        stats.accepted,
+        stats.rejected,
+        dense.len()
+    );
+    println!("End of run");
+    state1.print_deviation(&ground_truth(state, duration));
+    print_dense_deviation(state, &state1, &dense, samples);
+}
+
+/// Largest deviation of `dense` from IAS15 at `samples` random ticks between
+/// `state` and `state1`.
+fn print_dense_deviation(
+    state: &PhysicsState,
+    state1: &PhysicsState,
+    dense: &DenseOutput,
+    samples: usize,
+) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut ticks: Vec<u64> = (0..samples)
+        .map(|_| rng.random_range(0..=state1.t - state.t))
+        .collect();
+    ticks.sort();
+    let mut reference = state.clone();
+    let mut p_diff_max: f64 = 0.0;
+    let mut v_diff_max: f64 = 0.0;
+    for &tick in &ticks {
+        let elapsed = state.t + tick - reference.t;
+        if elapsed > 0 {
+            Ias15Kernel::simulate(&mut reference, 1, elapsed, 1);
+        }
+        let (p, v) = dense.evaluate(reference.time()).unwrap();
+        for i in 0..3 {
+            p_diff_max = p_diff_max.max((p[i] - reference.p[i]).norm());
+            v_diff_max = v_diff_max.max((v[i] - reference.v[i]).norm());
+        }
+    }
+    println!("Between steps, {} samples", samples);
+    println!("Position max deviation: {:.10e}", p_diff_max);
+    println!("Velocity max deviation: {:.10e}", v_diff_max);
+    println!("--------------------------------");
+}
+
+/// Locates the pericenter passages of body 1 and the node crossings of body 2
+/// of `eccentric_orbits` with `T`, and compares them with the times of the
+/// Kepler orbits. Then stops a run at body 1 passing 1.5 times its semi-major
+/// axis and compares the state there with the Kepler orbit.
+pub fn test_events<T: ThreeBodyKernel>(e: [f64; 2], steps_per_orbit: u64, orbits: u64) {
+    let (mut state, period) = eccentric_orbits(e);
+    let mu = util::GRAVITY_CONSTANT * util::MASS_SUN;
+    let n = 2.0 * std::f64::consts::PI / period;
+    let dt = (period / steps_per_orbit as f64 / util::UNIT_TIME) as u64;
+    let steps = steps_per_orbit * orbits;
+    let pi = std::f64::consts::PI;
+    // Both start at the eccentric anomaly pi / 2, mean anomaly pi / 2 - e.
+    let exact: [Vec<f64>; 2] = [
+        (0..orbits)
+            .map(|k| (1.5 * pi + e[0] + 2.0 * pi * k as f64) / n)
+            .collect(),
+        (0..2 * orbits)
+            .map(|k| (0.5 * pi + e[1] + pi * k as f64) / n)
+            .collect(),
+    ];
+    state.events = vec![
+        Arc::new(CloseApproach {
+            i: 0,
+            j: 1,
+            terminal: false,
+        }),
+        Arc::new(PlaneCrossing {
+            i: 2,
+            normal: Vec3::new(0.0, 0.0, 1.0),
+            offset: 0.0,
+            direction: Direction::Both,
+            terminal: false,
+        }),
+    ];
+
+    println!("--------------------------------");
+    println!("{}", std::any::type_name::<T>());
+    println!("steps per orbit = {}, dt = {}", steps_per_orbit, dt);
+    let mut state1 = state.clone();
+    let timer = std::time::Instant::now();
+    let log = T::simulate_events(&mut state1, steps, dt);
+    println!("time = {}ns", timer.elapsed().as_nanos());
+    for (k, name) in ["Pericenter", "Node"].iter().enumerate() {
+        let found: Vec<f64> = log.iter().filter(|e| e.event == k).map(|e| e.t).collect();
+        let mut max: f64 = 0.0;
+        for (t, t_exact) in found.iter().zip(&exact[k]) {
+            max = max.max((t - t_exact).abs());
+        }
+        println!(
+            "{}: found {} of {}, max time error = {:.5e}s",
+            name,
+            found.len(),
+            exact[k].len(),
+            max
+        );
+    }
+
+    let radius = 1.5 * util::AU;
+    state.events = vec![Arc::new(Escape {
+        i: 1,
+        radius: radius,
+        terminal: true,
+    })];
+    let mut state2 = state.clone();
+    let log = T::simulate_events(&mut state2, steps, dt);
+    let anomaly = (-(radius / util::AU - 1.0) / e[0]).acos();
+    let t_exact = (anomaly - e[0] * anomaly.sin() - (0.5 * pi - e[0])) / n;
+    println!(
+        "Escape: {} event, time error = {:.5e}s",
+        log.len(),
+        state2.time() - t_exact
+    );
+    let mut exact = state.clone();
+    for i in 1..3 {
+        n_body::kepler_drift(&mut exact.p[i], &mut exact.v[i], mu, state2.time());
+    }
+    state2.print_deviation(&exact);
+    println!("--------------------------------");
+}
+
+/// Checks the double-double reference of the error tests over `total_time`:
+/// against itself at twice the step, which bounds its truncation error, and
+/// against IAS15, and the `f64` Yoshida4 at the smallest step and at the
+/// reference step.
+pub fn test_double_double(state: &PhysicsState, total_time: u64) {
+    let timer = std::time::Instant::now();
+    let reference = reference_solution(state, total_time);
+    println!("--------------------------------");
+    println!("Double-double Yoshida4, dt = {}", REFERENCE_DT);
+    println!("time = {}ns", timer.elapsed().as_nanos());
+
+    let run = |name: &str, simulate: &dyn Fn(&mut PhysicsState)| {
+        let mut state1 = state.clone();
+        let timer = std::time::Instant::now();
+        simulate(&mut state1);
+        println!("--------------------------------");
+        println!("{}", name);
+        println!("time = {}ns", timer.elapsed().as_nanos());
+        state1.print_deviation(&reference);
+    };
+    let dt = 2 * REFERENCE_DT;
+    run("Double-double Yoshida4, dt = 2 * reference", &|state| {
+        Yoshida4Kernel::<Vec3DD>::simulate(state, 1, total_time / dt, dt)
+    });
+    run("IAS15", &|state| {
+        Ias15Kernel::simulate(state, 1, total_time, 1)
+    });
+    run("Yoshida4, dt = 1", &|state| {
+        <Yoshida4Kernel>::simulate(state, 1, total_time, 1)
+    });
+    run("Yoshida4, dt = reference", &|state| {
+        <Yoshida4Kernel>::simulate(state, 1, total_time / REFERENCE_DT, REFERENCE_DT)
+    });
+    println!("--------------------------------");
+}
+
+/// Benchmarks the N-body Yoshida4 and RK4 in single against double
+/// precision, for each step in `dts`. The errors are against the same method
+/// in double-double precision at the same step, so they are the round-off
+/// alone.
+pub fn test_precision(state: &PhysicsState, dts: &[u64], total_time: u64) {
+    for &dt in dts {
+        let steps = total_time / dt;
+        let mut yoshida4 = state.clone();
+        n_body::Yoshida4Kernel::<DirectSummation, Vec3DD>::default().simulate(
+            &mut yoshida4,
+            1,
+            steps,
+            dt,
+        );
+        let mut rk4 = state.clone();
+        n_body::RK4Kernel::<DirectSummation, Vec3DD>::default().simulate(&mut rk4, 1, steps, dt);
+
+        let run = |name: &str, reference: &PhysicsState, simulate: &dyn Fn(&mut PhysicsState)| {
+            let mut state1 = state.clone();
+            let timer = std::time::Instant::now();
+            simulate(&mut state1);
+            println!("--------------------------------");
+            println!("{}, dt = {}", name, dt);
+            println!("time = {}ns", timer.elapsed().as_nanos());
+            state1.print_deviation(reference);
+        };
+        run("Yoshida4 in f32", &yoshida4, &|state| {
+            n_body::Yoshida4Kernel::<DirectSummation, Vec3f>::default()
+                .simulate(state, 1, steps, dt)
+        });
+        run("Yoshida4 in f64", &yoshida4, &|state| {
+            <n_body::Yoshida4Kernel>::default().simulate(state, 1, steps, dt)
+        });
+        run("RK4 in f32", &rk4, &|state| {
+            n_body::RK4Kernel::<DirectSummation, Vec3f>::default().simulate(state, 1, steps, dt)
+        });
+        run("RK4 in f64", &rk4, &|state| {
+            <n_body::RK4Kernel>::default().simulate(state, 1, steps, dt)
+        });
+    }
+    println!("--------------------------------");
+}
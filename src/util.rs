@@ -4,4 +4,6 @@ pub const AU: f64 = 1.495978707e11;
 pub const MASS_SUN: f64 = 1.998416e30;
 pub const MASS_EARTH: f64 = 5.9722e24;
 pub const SPEED_LIGHT: f64 = 2.99792458e8;
+pub const COULOMB_CONSTANT: f64 = 8.9875517862e9;
 pub const YEAR: f64 = 365.25 * 86400.0;
+pub const PARSEC: f64 = 3.085677581491367e16;
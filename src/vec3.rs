@@ -30,8 +30,8 @@ impl Vec3 {
 
     #[must_use]
     #[inline]
-    fn to_array(&self) -> [f64; 4] {
-        unsafe { core::mem::transmute(*self) }
+    fn to_array(self) -> [f64; 4] {
+        unsafe { core::mem::transmute(self) }
     }
 }
 
@@ -41,7 +41,7 @@ impl Vec3 {
     #[inline]
     pub fn new(x: f64, y: f64, z: f64) -> Self {
         Vec3 {
-            avx: unsafe { core::mem::transmute([x, y, z, 0.0]) },
+            avx: unsafe { core::mem::transmute::<[f64; 4], __m256d>([x, y, z, 0.0]) },
         }
     }
 
@@ -310,8 +310,7 @@ unsafe fn reduce_add0(m: __m256d) -> __m128d {
     let high128 = _mm256_extractf128_pd::<1>(m);
     let add1 = _mm_add_pd(low128, high128);
     let high64 = _mm_unpackhi_pd(add1, add1);
-    let mag = _mm_add_pd(add1, high64);
-    mag
+    _mm_add_pd(add1, high64)
 }
 
 impl Vec3 {
@@ -0,0 +1,455 @@
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+use core::fmt::Debug;
+use core::fmt::Display;
+use core::ops::*;
+
+use crate::Vec3;
+
+/// `Vec3` in single precision, in an SSE `__m128` in the AVX build, for the
+/// kernels generic over precision where `f32` is accurate enough.
+#[cfg(target_feature = "avx2")]
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct Vec3f {
+    pub sse: __m128,
+}
+
+/// `Vec3` in single precision, for the kernels generic over precision where
+/// `f32` is accurate enough.
+#[cfg(not(target_feature = "avx2"))]
+#[derive(Default, Clone, Copy, PartialEq)]
+pub struct Vec3f {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[cfg(target_feature = "avx2")]
+impl Vec3f {
+    #[must_use]
+    #[inline]
+    const fn from_array(arr: [f32; 4]) -> Self {
+        unsafe { core::mem::transmute(arr) }
+    }
+
+    #[must_use]
+    #[inline]
+    fn to_array(self) -> [f32; 4] {
+        unsafe { core::mem::transmute(self) }
+    }
+}
+
+impl Vec3f {
+    #[cfg(target_feature = "avx2")]
+    #[must_use]
+    #[inline]
+    pub fn new(x: f32, y: f32, z: f32) -> Self {
+        Vec3f {
+            sse: unsafe { core::mem::transmute::<[f32; 4], __m128>([x, y, z, 0.0]) },
+        }
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    #[must_use]
+    #[inline]
+    pub fn new(x: f32, y: f32, z: f32) -> Self {
+        Vec3f { x, y, z }
+    }
+
+    pub fn splat(value: f32) -> Self {
+        Self::new(value, value, value)
+    }
+}
+
+impl From<Vec3f> for [f32; 3] {
+    #[cfg(target_feature = "avx2")]
+    #[inline]
+    fn from(value: Vec3f) -> Self {
+        let arr = value.to_array();
+        [arr[0], arr[1], arr[2]]
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    #[inline]
+    fn from(value: Vec3f) -> Self {
+        [value.x, value.y, value.z]
+    }
+}
+
+impl From<Vec3> for Vec3f {
+    #[inline]
+    fn from(value: Vec3) -> Self {
+        let [x, y, z] = <[f64; 3]>::from(value);
+        Vec3f::new(x as f32, y as f32, z as f32)
+    }
+}
+
+impl From<Vec3f> for Vec3 {
+    #[inline]
+    fn from(value: Vec3f) -> Self {
+        let [x, y, z] = <[f32; 3]>::from(value);
+        Vec3::new(x as f64, y as f64, z as f64)
+    }
+}
+
+#[cfg(target_feature = "avx2")]
+impl Vec3f {
+    pub const ZERO: Vec3f = Vec3f::from_array([0.0; 4]);
+    pub const ONE: Vec3f = Vec3f::from_array([1.0; 4]);
+}
+
+#[cfg(not(target_feature = "avx2"))]
+impl Vec3f {
+    pub const ZERO: Vec3f = Vec3f {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    pub const ONE: Vec3f = Vec3f {
+        x: 1.0,
+        y: 1.0,
+        z: 1.0,
+    };
+}
+
+#[cfg(target_feature = "avx2")]
+impl Default for Vec3f {
+    #[inline]
+    fn default() -> Self {
+        Vec3f {
+            sse: unsafe { _mm_setzero_ps() },
+        }
+    }
+}
+
+#[cfg(target_feature = "avx2")]
+impl PartialEq for Vec3f {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        unsafe {
+            let cmp1 = _mm_cmpeq_ps(self.sse, other.sse);
+            let mask = _mm_movemask_ps(cmp1);
+            mask == 0b1111
+        }
+    }
+}
+
+impl Debug for Vec3f {
+    #[cfg(target_feature = "avx2")]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let arr: [f32; 4] = self.to_array();
+        write!(
+            f,
+            "Vec3f({:.10e}, {:.10e}, {:.10e})",
+            arr[0], arr[1], arr[2]
+        )
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Vec3f({:.10e}, {:.10e}, {:.10e})",
+            self.x, self.y, self.z
+        )
+    }
+}
+
+impl Display for Vec3f {
+    #[cfg(target_feature = "avx2")]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let arr: [f32; 4] = self.to_array();
+        write!(f, "({:.10e}, {:.10e}, {:.10e})", arr[0], arr[1], arr[2])
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "({:.10e}, {:.10e}, {:.10e})", self.x, self.y, self.z)
+    }
+}
+
+impl Add for Vec3f {
+    type Output = Self;
+
+    #[cfg(target_feature = "avx2")]
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        unsafe {
+            Vec3f {
+                sse: _mm_add_ps(self.sse, rhs.sse),
+            }
+        }
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        Vec3f {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl Sub for Vec3f {
+    type Output = Self;
+
+    #[cfg(target_feature = "avx2")]
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        unsafe {
+            Vec3f {
+                sse: _mm_sub_ps(self.sse, rhs.sse),
+            }
+        }
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        Vec3f {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
+
+impl Mul<f32> for Vec3f {
+    type Output = Self;
+
+    #[cfg(target_feature = "avx2")]
+    #[inline]
+    fn mul(self, rhs: f32) -> Self {
+        unsafe {
+            let scalar = _mm_set1_ps(rhs);
+            Vec3f {
+                sse: _mm_mul_ps(self.sse, scalar),
+            }
+        }
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    #[inline]
+    fn mul(self, rhs: f32) -> Self {
+        Vec3f {
+            x: self.x * rhs,
+            y: self.y * rhs,
+            z: self.z * rhs,
+        }
+    }
+}
+
+impl Mul<Vec3f> for f32 {
+    type Output = Vec3f;
+    fn mul(self, rhs: Vec3f) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl Mul for Vec3f {
+    type Output = Self;
+
+    #[cfg(target_feature = "avx2")]
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        unsafe {
+            Vec3f {
+                sse: _mm_mul_ps(self.sse, rhs.sse),
+            }
+        }
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        Vec3f {
+            x: self.x * rhs.x,
+            y: self.y * rhs.y,
+            z: self.z * rhs.z,
+        }
+    }
+}
+
+impl Div<f32> for Vec3f {
+    type Output = Self;
+
+    #[cfg(target_feature = "avx2")]
+    #[inline]
+    fn div(self, rhs: f32) -> Self {
+        unsafe {
+            let scalar = _mm_set1_ps(rhs);
+            Vec3f {
+                sse: _mm_div_ps(self.sse, scalar),
+            }
+        }
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    #[inline]
+    fn div(self, rhs: f32) -> Self {
+        Vec3f {
+            x: self.x / rhs,
+            y: self.y / rhs,
+            z: self.z / rhs,
+        }
+    }
+}
+
+impl Div for Vec3f {
+    type Output = Self;
+
+    #[cfg(target_feature = "avx2")]
+    #[inline]
+    fn div(self, rhs: Self) -> Self {
+        unsafe {
+            Vec3f {
+                sse: _mm_div_ps(self.sse, rhs.sse),
+            }
+        }
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    #[inline]
+    fn div(self, rhs: Self) -> Self {
+        Vec3f {
+            x: self.x / rhs.x,
+            y: self.y / rhs.y,
+            z: self.z / rhs.z,
+        }
+    }
+}
+
+impl Neg for Vec3f {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        Self::ZERO - self
+    }
+}
+
+#[cfg(target_feature = "avx2")]
+#[inline]
+#[must_use]
+unsafe fn reduce_add0(m: __m128) -> __m128 {
+    let odd = _mm_movehdup_ps(m);
+    let add1 = _mm_add_ps(m, odd);
+    let high = _mm_movehl_ps(odd, add1);
+    _mm_add_ss(add1, high)
+}
+
+impl Vec3f {
+    #[cfg(target_feature = "avx2")]
+    #[inline]
+    #[must_use]
+    pub fn reduce_add(&self) -> f32 {
+        unsafe { _mm_cvtss_f32(reduce_add0(self.sse)) }
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    #[inline]
+    #[must_use]
+    pub fn reduce_add(&self) -> f32 {
+        self.x + self.y + self.z
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn norm_squared(&self) -> f32 {
+        let squared = self * self;
+        squared.reduce_add()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn norm(&self) -> f32 {
+        self.norm_squared().sqrt()
+    }
+}
+
+impl Vec3f {
+    #[cfg(all(target_feature = "avx2", target_feature = "fma"))]
+    #[inline]
+    #[must_use]
+    pub fn mul_add(a: Vec3f, b: Vec3f, c: Vec3f) -> Vec3f {
+        unsafe {
+            Vec3f {
+                sse: _mm_fmadd_ps(a.sse, b.sse, c.sse),
+            }
+        }
+    }
+
+    #[cfg(not(all(target_feature = "avx2", target_feature = "fma")))]
+    #[inline]
+    #[must_use]
+    pub fn mul_add(a: Vec3f, b: Vec3f, c: Vec3f) -> Vec3f {
+        a * b + c
+    }
+
+    #[cfg(all(target_feature = "avx2", target_feature = "fma"))]
+    #[inline]
+    #[must_use]
+    pub fn mul_sub(a: Vec3f, b: Vec3f, c: Vec3f) -> Vec3f {
+        unsafe {
+            Vec3f {
+                sse: _mm_fmsub_ps(a.sse, b.sse, c.sse),
+            }
+        }
+    }
+
+    #[cfg(not(all(target_feature = "avx2", target_feature = "fma")))]
+    #[inline]
+    #[must_use]
+    pub fn mul_sub(a: Vec3f, b: Vec3f, c: Vec3f) -> Vec3f {
+        a * b - c
+    }
+
+    #[cfg(all(target_feature = "avx2", target_feature = "fma"))]
+    #[inline]
+    #[must_use]
+    pub fn mul_neg_add(a: Vec3f, b: Vec3f, c: Vec3f) -> Vec3f {
+        unsafe {
+            Vec3f {
+                sse: _mm_fnmadd_ps(a.sse, b.sse, c.sse),
+            }
+        }
+    }
+
+    #[cfg(not(all(target_feature = "avx2", target_feature = "fma")))]
+    #[inline]
+    #[must_use]
+    pub fn mul_neg_add(a: Vec3f, b: Vec3f, c: Vec3f) -> Vec3f {
+        -a * b + c
+    }
+
+    #[cfg(all(target_feature = "avx2", target_feature = "fma"))]
+    #[inline]
+    #[must_use]
+    pub fn mul_neg_sub(a: Vec3f, b: Vec3f, c: Vec3f) -> Vec3f {
+        unsafe {
+            Vec3f {
+                sse: _mm_fnmsub_ps(a.sse, b.sse, c.sse),
+            }
+        }
+    }
+
+    #[cfg(not(all(target_feature = "avx2", target_feature = "fma")))]
+    #[inline]
+    #[must_use]
+    pub fn mul_neg_sub(a: Vec3f, b: Vec3f, c: Vec3f) -> Vec3f {
+        -a * b - c
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn calc_r(p1: &Vec3f, p2: &Vec3f) -> Vec3f {
+        let r = p2 - p1;
+        let r2 = r.norm_squared();
+        let mag = r2 * r2.sqrt();
+        return r / mag;
+    }
+}
@@ -0,0 +1,236 @@
+use core::fmt::Debug;
+use core::ops::*;
+
+use crate::{DoubleDouble, Vec3, Vec3DD, Vec3f};
+
+/// Scalar of a `Vector`.
+pub trait Scalar:
+    Copy
+    + Debug
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn sqrt(self) -> Self;
+}
+
+/// The part of the `Vec3` API used by the kernels generic over precision, so
+/// that one source runs in single (`Vec3f`), double (`Vec3`) or double-double
+/// (`Vec3DD`) precision.
+pub trait Vector:
+    Copy
+    + Debug
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Mul<Self::Scalar, Output = Self>
+    + Div<Self::Scalar, Output = Self>
+    + Neg<Output = Self>
+{
+    type Scalar: Scalar;
+
+    const ZERO: Self;
+
+    fn splat(value: Self::Scalar) -> Self;
+    fn mul_add(a: Self, b: Self, c: Self) -> Self;
+    fn mul_neg_add(a: Self, b: Self, c: Self) -> Self;
+    fn norm_squared(&self) -> Self::Scalar;
+    fn norm(&self) -> Self::Scalar;
+    fn calc_r(p1: &Self, p2: &Self) -> Self;
+    fn from_vec3(value: Vec3) -> Self;
+    fn to_vec3(self) -> Vec3;
+}
+
+impl Scalar for f64 {
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    #[inline]
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+}
+
+impl Scalar for f32 {
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    #[inline]
+    fn sqrt(self) -> Self {
+        f32::sqrt(self)
+    }
+}
+
+impl Scalar for DoubleDouble {
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        DoubleDouble::from(value)
+    }
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        DoubleDouble::to_f64(self)
+    }
+
+    #[inline]
+    fn sqrt(self) -> Self {
+        DoubleDouble::sqrt(self)
+    }
+}
+
+impl Vector for Vec3 {
+    type Scalar = f64;
+
+    const ZERO: Self = Vec3::ZERO;
+
+    #[inline]
+    fn splat(value: f64) -> Self {
+        Vec3::splat(value)
+    }
+
+    #[inline]
+    fn mul_add(a: Self, b: Self, c: Self) -> Self {
+        Vec3::mul_add(a, b, c)
+    }
+
+    #[inline]
+    fn mul_neg_add(a: Self, b: Self, c: Self) -> Self {
+        Vec3::mul_neg_add(a, b, c)
+    }
+
+    #[inline]
+    fn norm_squared(&self) -> f64 {
+        Vec3::norm_squared(self)
+    }
+
+    #[inline]
+    fn norm(&self) -> f64 {
+        Vec3::norm(self)
+    }
+
+    #[inline]
+    fn calc_r(p1: &Self, p2: &Self) -> Self {
+        Vec3::calc_r(p1, p2)
+    }
+
+    #[inline]
+    fn from_vec3(value: Vec3) -> Self {
+        value
+    }
+
+    #[inline]
+    fn to_vec3(self) -> Vec3 {
+        self
+    }
+}
+
+impl Vector for Vec3f {
+    type Scalar = f32;
+
+    const ZERO: Self = Vec3f::ZERO;
+
+    #[inline]
+    fn splat(value: f32) -> Self {
+        Vec3f::splat(value)
+    }
+
+    #[inline]
+    fn mul_add(a: Self, b: Self, c: Self) -> Self {
+        Vec3f::mul_add(a, b, c)
+    }
+
+    #[inline]
+    fn mul_neg_add(a: Self, b: Self, c: Self) -> Self {
+        Vec3f::mul_neg_add(a, b, c)
+    }
+
+    #[inline]
+    fn norm_squared(&self) -> f32 {
+        Vec3f::norm_squared(self)
+    }
+
+    #[inline]
+    fn norm(&self) -> f32 {
+        Vec3f::norm(self)
+    }
+
+    #[inline]
+    fn calc_r(p1: &Self, p2: &Self) -> Self {
+        Vec3f::calc_r(p1, p2)
+    }
+
+    #[inline]
+    fn from_vec3(value: Vec3) -> Self {
+        Vec3f::from(value)
+    }
+
+    #[inline]
+    fn to_vec3(self) -> Vec3 {
+        Vec3::from(self)
+    }
+}
+
+impl Vector for Vec3DD {
+    type Scalar = DoubleDouble;
+
+    const ZERO: Self = Vec3DD::ZERO;
+
+    #[inline]
+    fn splat(value: DoubleDouble) -> Self {
+        Vec3DD::splat(value)
+    }
+
+    #[inline]
+    fn mul_add(a: Self, b: Self, c: Self) -> Self {
+        Vec3DD::mul_add(a, b, c)
+    }
+
+    #[inline]
+    fn mul_neg_add(a: Self, b: Self, c: Self) -> Self {
+        Vec3DD::mul_neg_add(a, b, c)
+    }
+
+    #[inline]
+    fn norm_squared(&self) -> DoubleDouble {
+        Vec3DD::norm_squared(self)
+    }
+
+    #[inline]
+    fn norm(&self) -> DoubleDouble {
+        Vec3DD::norm(self)
+    }
+
+    #[inline]
+    fn calc_r(p1: &Self, p2: &Self) -> Self {
+        Vec3DD::calc_r(p1, p2)
+    }
+
+    #[inline]
+    fn from_vec3(value: Vec3) -> Self {
+        Vec3DD::from(value)
+    }
+
+    #[inline]
+    fn to_vec3(self) -> Vec3 {
+        Vec3::from(self)
+    }
+}
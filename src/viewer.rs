@@ -79,8 +79,8 @@ pub fn start_viewer<T: ThreeBodyKernel>(initial_state: PhysicsState, dt: u64, st
         last_time = Instant::now();
 
         for event in window.events().iter() {
-            match event.value {
-                WindowEvent::Key(key, Action::Press, _) => match key {
+            if let WindowEvent::Key(key, Action::Press, _) = event.value {
+                match key {
                     Key::Add | Key::Equals => {
                         // camera.set_dist(camera.dist() / 1.5);
                         dt_ratio *= 1.5;
@@ -96,8 +96,7 @@ pub fn start_viewer<T: ThreeBodyKernel>(initial_state: PhysicsState, dt: u64, st
                         camera.look_at(Point3::new(2.0, 2.0, 2.0), Point3::origin());
                     }
                     _ => {}
-                },
-                _ => {}
+                }
             }
         }
 
@@ -150,7 +149,7 @@ pub fn start_viewer<T: ThreeBodyKernel>(initial_state: PhysicsState, dt: u64, st
                 sim_time.as_micros(),
                 (step_count * dt) as f64 * dt_ratio * util::UNIT_TIME / sim_time.as_secs_f64(),
                 (dt as f64 * dt_ratio) as u64,
-                state.t as f64 * util::UNIT_TIME / util::YEAR,
+                state.time() / util::YEAR,
             )
             .as_str(),
             &Point2::new(20.0, 10.0),